@@ -1,6 +1,9 @@
 use proc_macro::TokenStream;
-use quote::{quote, quote_spanned};
-use syn::{parse_macro_input, spanned::Spanned, Data, DataStruct, DeriveInput};
+use quote::{format_ident, quote, quote_spanned};
+use syn::{
+    parse_macro_input, parse_quote, spanned::Spanned, Data, DataStruct, DeriveInput, Field, Fields,
+    Meta, Type,
+};
 
 pub fn bundle_derive(item: TokenStream) -> TokenStream {
     let item = parse_macro_input!(item as DeriveInput);
@@ -15,41 +18,175 @@ pub fn bundle_derive(item: TokenStream) -> TokenStream {
 }
 
 fn impl_struct(data: &DataStruct, item: &DeriveInput) -> TokenStream {
+    if data.fields.is_empty() {
+        return TokenStream::from(
+            quote_spanned! { item.span() => compile_error!("bundles need at least one field") },
+        );
+    }
+
+    let mut flatten = Vec::new();
+    for field in data.fields.iter() {
+        match is_flattened(field) {
+            Ok(x) => flatten.push(x),
+            Err(e) => return e.to_compile_error().into(),
+        }
+    }
+
     let types = data.fields.iter().map(|x| &x.ty).collect::<Vec<_>>();
-    let names = data.fields.iter().map(|x| &x.ident).collect::<Vec<_>>();
+    if let Err(e) = check_duplicates(&types, &flatten) {
+        return e.to_compile_error().into();
+    }
+    let members = data.fields.members().collect::<Vec<_>>();
+    let field_vis = data.fields.iter().map(|x| &x.vis).collect::<Vec<_>>();
 
-    let generics = &item.generics;
-    let where_clause = &item.generics.where_clause;
     let name = &item.ident;
+    let vis = &item.vis;
+    // the references handed out by queries and World::get
+    let ref_name = format_ident!("{}Ref", name);
+    let mut_name = format_ident!("{}Mut", name);
+
+    // components have to be 'static to get a TypeId, nested bundles bring their own
+    let mut generics = item.generics.clone();
+    generics.params.insert(0, parse_quote!('__bundle));
+    let where_clause = generics.make_where_clause();
+    for (ty, flatten) in types.iter().zip(flatten.iter()) {
+        match flatten {
+            true => where_clause
+                .predicates
+                .push(parse_quote!(#ty: perplecs::prelude::Bundle<'__bundle>)),
+            false => where_clause.predicates.push(parse_quote!(#ty: 'static)),
+        }
+    }
+    let (impl_generics, target_generics, where_clause) = generics.split_for_impl();
+    let (_, ty_generics, _) = item.generics.split_for_impl();
+
+    let mut counts = Vec::new();
+    let mut ref_types = Vec::new();
+    let mut mut_types = Vec::new();
+    let mut type_info = Vec::new();
+    let mut type_ids = Vec::new();
+    let mut component_info = Vec::new();
+    let mut as_ptrs = Vec::new();
+    let mut from_ptr = Vec::new();
+    let mut from_ptr_mut = Vec::new();
+    for ((ty, member), flatten) in types.iter().zip(members.iter()).zip(flatten.iter()) {
+        if *flatten {
+            let bundle = quote! { <#ty as perplecs::prelude::Bundle<'__bundle>> };
+            counts.push(quote! { #bundle::COUNT });
+            ref_types.push(quote! { #bundle::Target });
+            mut_types.push(quote! { #bundle::TargetMut });
+            type_info.push(quote! { info.extend_from_slice(&#bundle::type_info()); });
+            type_ids.push(quote! { ids.extend_from_slice(&#bundle::type_ids()); });
+            component_info.push(quote! { info.extend(#bundle::component_info::<__A>()); });
+            as_ptrs.push(quote! { ptrs.extend_from_slice(&#bundle::as_ptrs(&mut self.#member)); });
+            // a nested bundle takes as many pointers as it has components
+            let take = quote! {
+                let len = #bundle::COUNT;
+                let data_ = &data[offset..offset + len];
+                offset += len;
+            };
+            from_ptr.push(quote! { #member: { #take #bundle::from_ptr(data_) } });
+            from_ptr_mut.push(quote! { #member: { #take #bundle::from_ptr_mut(data_) } });
+        } else {
+            counts.push(quote! { 1 });
+            ref_types.push(quote! { &'__bundle #ty });
+            mut_types.push(quote! { &'__bundle mut #ty });
+            type_info.push(quote! { info.push(perplecs::archetype::TypeInfo::of::<#ty>()); });
+            type_ids.push(quote! { ids.push(core::any::TypeId::of::<#ty>()); });
+            component_info
+                .push(quote! { info.push(perplecs::component::ComponentInfo::new::<#ty>()); });
+            as_ptrs
+                .push(quote! { ptrs.push(perplecs::bundle::into_ptr::<#ty>(&mut self.#member)); });
+            let take = quote! {
+                let data_ = data[offset];
+                offset += 1;
+            };
+            from_ptr.push(quote! { #member: { #take perplecs::bundle::from_ptr::<#ty>(data_) } });
+            from_ptr_mut
+                .push(quote! { #member: { #take perplecs::bundle::from_ptr_mut::<#ty>(data_) } });
+        }
+    }
+
+    let (ref_fields, mut_fields) = match data.fields {
+        Fields::Named(_) => (
+            quote! { { #(#field_vis #members: #ref_types),* } },
+            quote! { { #(#field_vis #members: #mut_types),* } },
+        ),
+        _ => (
+            quote! { ( #(#field_vis #ref_types),* ) #where_clause; },
+            quote! { ( #(#field_vis #mut_types),* ) #where_clause; },
+        ),
+    };
+    // tuple structs take the where clause after their fields
+    let decl_where_clause = match data.fields {
+        Fields::Named(_) => Some(where_clause),
+        _ => None,
+    };
+    // only nested bundles can bring in the same component twice
+    let assert_unique = match flatten.contains(&true) {
+        true => Some(quote! { perplecs::bundle::assert_unique(stringify!(#name), &info); }),
+        false => None,
+    };
+    let assert_unique_info = match flatten.contains(&true) {
+        true => Some(quote! {
+            let types = info.iter().map(|x| x.type_info).collect::<Vec<_>>();
+            perplecs::bundle::assert_unique(stringify!(#name), &types);
+        }),
+        false => None,
+    };
 
     let output = quote! {
-        impl #generics perplecs::prelude::Bundle<'_> for #name #generics #where_clause {
-            type Target = ();
-            type TargetMut = ();
+        #[allow(dead_code)]
+        #vis struct #ref_name #impl_generics #decl_where_clause #ref_fields
+
+        #[allow(dead_code)]
+        #vis struct #mut_name #impl_generics #decl_where_clause #mut_fields
+
+        impl #impl_generics perplecs::prelude::Bundle<'__bundle> for #name #ty_generics #where_clause {
+            type Target = #ref_name #target_generics;
+            type TargetMut = #mut_name #target_generics;
+            const COUNT: usize = 0 #(+ #counts)*;
             fn type_info() -> Box<[perplecs::archetype::TypeInfo]> {
-                Box::new([
-                    #(perplecs::archetype::TypeInfo::new::<#types>()),*
-                ])
+                let mut info = Vec::new();
+                #(#type_info)*
+                #assert_unique
+                info.into_boxed_slice()
             }
 
             fn type_ids() -> Box<[core::any::TypeId]> {
-                Box::new([
-                    #(core::any::TypeId::of::<#types>()),*
-                ])
+                let mut ids = Vec::new();
+                #(#type_ids)*
+                ids.into_boxed_slice()
+            }
+
+            fn component_info<__A: perplecs::world::WorldAllocator>(
+            ) -> Vec<perplecs::component::ComponentInfo<__A>> {
+                let mut info = Vec::new();
+                #(#component_info)*
+                #assert_unique_info
+                info
             }
 
             unsafe fn as_ptrs(&mut self) -> Box<[*mut u8]> {
-                Box::new([
-                    #(perplecs::bundle::into_ptr::<#types>(&mut self.#names)),*
-                ])
+                let mut ptrs = Vec::new();
+                #(#as_ptrs)*
+                ptrs.into_boxed_slice()
             }
 
+            #[allow(unused_assignments)]
             unsafe fn from_ptr(data: &[*mut u8]) -> Self::Target {
-                ()
+                let mut offset = 0;
+                #ref_name {
+                    #(#from_ptr),*
+                }
             }
 
+            #[allow(unused_assignments)]
             unsafe fn from_ptr_mut(data: &[*mut u8]) -> Self::TargetMut {
-                ()
+                let mut offset = 0;
+                #mut_name {
+                    #(#from_ptr_mut),*
+                }
             }
         }
     };
@@ -57,6 +194,46 @@ fn impl_struct(data: &DataStruct, item: &DeriveInput) -> TokenStream {
     return output.into();
 }
 
+//the same component twice would end up in one archetype column. Types are compared
+//by how they are spelled, so only identical spellings are caught here: an alias or
+//a path to the same type gets through and is caught by the world at runtime.
+fn check_duplicates(types: &[&Type], flatten: &[bool]) -> syn::Result<()> {
+    let mut seen = Vec::new();
+    for (ty, flatten) in types.iter().zip(flatten.iter()) {
+        // nested bundles only know their components at runtime
+        if *flatten {
+            continue;
+        }
+        let name = quote!(#ty).to_string();
+        if seen.contains(&name) {
+            return Err(syn::Error::new(
+                ty.span(),
+                format!("the component `{}` is already part of this bundle", name),
+            ));
+        }
+        seen.push(name);
+    }
+    return Ok(());
+}
+
+//`#[bundle]` and `#[bundle(flatten)]` both splice a nested bundle into the parent
+fn is_flattened(field: &Field) -> syn::Result<bool> {
+    let mut flatten = false;
+    for attr in field.attrs.iter().filter(|x| x.path().is_ident("bundle")) {
+        match &attr.meta {
+            Meta::Path(_) => flatten = true,
+            _ => attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("flatten") {
+                    flatten = true;
+                    return Ok(());
+                }
+                return Err(meta.error("unknown bundle attribute, expected `flatten`"));
+            })?,
+        }
+    }
+    return Ok(flatten);
+}
+
 fn impl_enum(item: &DeriveInput) -> TokenStream {
     return TokenStream::from(
         quote_spanned! { item.span() => compile_error!("enums can't be used as bundles") },
@@ -0,0 +1,94 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, DeriveInput, LitStr, Path};
+
+pub fn component_derive(item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as DeriveInput);
+    let attributes = match ComponentAttributes::parse(&item) {
+        Ok(x) => x,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    let name = &item.ident;
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+
+    let storage = attributes.storage.map(|x| {
+        quote! {
+            const STORAGE: perplecs::component::StorageType = perplecs::component::StorageType::#x;
+        }
+    });
+    let component_name = attributes.name.map(|x| {
+        quote! {
+            fn name() -> &'static str {
+                #x
+            }
+        }
+    });
+    let on_add = attributes.on_add.iter();
+    let on_insert = attributes.on_insert.iter();
+    let on_remove = attributes.on_remove.iter();
+
+    let output = quote! {
+        impl #impl_generics perplecs::component::Component for #name #ty_generics #where_clause {
+            #storage
+
+            #component_name
+
+            fn register_hooks<A>(hooks: &mut perplecs::component::ComponentHooks<A>) -> ()
+            where
+                A: perplecs::world::WorldAllocator,
+            {
+                #(hooks.on_add(#on_add);)*
+                #(hooks.on_insert(#on_insert);)*
+                #(hooks.on_remove(#on_remove);)*
+            }
+        }
+    };
+
+    return output.into();
+}
+
+#[derive(Default)]
+struct ComponentAttributes {
+    storage: Option<syn::Ident>,
+    name: Option<LitStr>,
+    on_add: Option<Path>,
+    on_insert: Option<Path>,
+    on_remove: Option<Path>,
+}
+
+impl ComponentAttributes {
+    fn parse(item: &DeriveInput) -> syn::Result<Self> {
+        let mut attributes = Self::default();
+        for attr in item.attrs.iter().filter(|x| x.path().is_ident("component")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("storage") {
+                    let storage = meta.value()?.parse::<LitStr>()?;
+                    let variant = match storage.value().as_str() {
+                        "table" => "Table",
+                        "sparse" => "Sparse",
+                        _ => {
+                            return Err(syn::Error::new(
+                                storage.span(),
+                                "unknown storage, expected `table` or `sparse`",
+                            ))
+                        }
+                    };
+                    attributes.storage = Some(syn::Ident::new(variant, storage.span()));
+                } else if meta.path.is_ident("name") {
+                    attributes.name = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("on_add") {
+                    attributes.on_add = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("on_insert") {
+                    attributes.on_insert = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("on_remove") {
+                    attributes.on_remove = Some(meta.value()?.parse()?);
+                } else {
+                    return Err(meta.error("unknown component attribute"));
+                }
+                return Ok(());
+            })?;
+        }
+        return Ok(attributes);
+    }
+}
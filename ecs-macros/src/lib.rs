@@ -1,8 +1,14 @@
 use proc_macro::TokenStream;
 
 mod bundle;
+mod component;
 
-#[proc_macro_derive(Bundle)]
+#[proc_macro_derive(Bundle, attributes(bundle))]
 pub fn bundle_derive(item: TokenStream) -> TokenStream {
     return bundle::bundle_derive(item.into()).into();
 }
+
+#[proc_macro_derive(Component, attributes(component))]
+pub fn component_derive(item: TokenStream) -> TokenStream {
+    return component::component_derive(item);
+}
@@ -8,16 +8,23 @@ use core::{
 use alloc::{boxed::Box, vec::Vec};
 use rustc_hash::FxHashMap;
 
-use crate::entity::Entity;
+use crate::{
+    component::{Component, ComponentId, ComponentSet, Metadata, StorageType},
+    entity::Entity,
+};
 
 #[derive(Clone, Debug)]
 pub struct Archetype<A>
 where
     A: Allocator,
 {
-    pub(crate) types: FxHashMap<TypeId, TypeInfo>,
-    pub(crate) type_ids: Box<[TypeId]>,
+    //sorted, the type info and data of a component share its position
+    pub(crate) component_ids: Box<[ComponentId]>,
+    pub(crate) type_info: Box<[TypeInfo]>,
+    mask: ComponentSet,
     pub(crate) entities: Vec<Entity>, //Box<[Entity]>,
+    //the row of every entity in `entities`
+    rows: FxHashMap<Entity, usize>,
     capacity: usize,
     pub(crate) data: Box<[ComponentData<A>]>,
     pub(crate) allocator: A,
@@ -27,24 +34,24 @@ impl<A> Archetype<A>
 where
     A: Allocator,
 {
-    pub fn new(type_ids: &[TypeId], type_info: &[TypeInfo], allocator: A) -> Self {
-        let mut types = FxHashMap::default();
-        for i in type_info {
-            types.insert(i.id, *i);
-        }
-
+    pub fn new(component_ids: &[ComponentId], type_info: &[TypeInfo], allocator: A) -> Self {
+        debug_assert!(component_ids.is_sorted());
         let mut archetype = Self {
-            types,
-            type_ids: type_ids.into(),
+            component_ids: component_ids.into(),
+            type_info: type_info.into(),
+            mask: ComponentSet::from_ids(component_ids),
             entities: Vec::new(),
+            rows: FxHashMap::default(),
             capacity: 16,
             data: Box::new([]),
             allocator,
         };
 
-        let mut data = Vec::with_capacity(type_ids.len());
-        for i in type_ids {
-            data.push(unsafe { Self::alloc(&archetype, *i, archetype.capacity) });
+        let mut data = Vec::with_capacity(component_ids.len());
+        for i in type_info {
+            data.push(unsafe {
+                ComponentData::new(i.layout, archetype.capacity, &archetype.allocator)
+            });
         }
         archetype.data = data.into_boxed_slice();
         return archetype;
@@ -64,146 +71,196 @@ where
 
     pub unsafe fn add(&mut self, entity: Entity, data: &[*mut u8]) -> () {
         if self.entities.len() >= self.capacity {
-            self.grow(self.capacity * 2);
+            self.grow((self.capacity * 2).max(1));
         }
 
-        for (i, (ty, data)) in self.type_ids.iter().zip(data.iter()).enumerate() {
-            let ty = &self.types[ty];
+        for (i, (ty, data)) in self.type_info.iter().zip(data.iter()).enumerate() {
             self.data[i].set(ty, self.len(), *data);
         }
 
+        self.rows.insert(entity, self.len());
         self.entities.push(entity);
         // pushing after the data adding otherwise we would get a off by plus one
     }
 
-    //removes the specified type_ids and returns the data for the unspecefied ones
-    pub unsafe fn remove(&mut self, entity: Entity, type_id: &[TypeId]) -> Option<Box<[*mut u8]>> {
-        let index = self.entities.binary_search(&entity);
-        if index.is_err() {
-            return None;
+    /// Adds rows for `entities` and returns where their values go in each column.
+    ///
+    /// The caller has to write every value before the archetype gets used again.
+    pub unsafe fn push_uninit(&mut self, entities: &[Entity]) -> Vec<*mut u8> {
+        self.reserve(entities.len());
+        let start = self
+            .data
+            .iter()
+            .zip(self.type_info.iter())
+            .map(|(data, ty)| data.get(ty, self.len()))
+            .collect();
+        for entity in entities {
+            self.rows.insert(*entity, self.len());
+            self.entities.push(*entity);
         }
+        return start;
+    }
+
+    pub fn reserve(&mut self, additional: usize) -> () {
+        let needed = self.len() + additional;
+        if needed > self.capacity {
+            unsafe { self.grow(needed.max(self.capacity * 2)) };
+        }
+    }
+
+    //drops every entity but keeps the columns around for reuse
+    pub fn clear(&mut self) -> () {
+        for (data, ty) in self.data.iter().zip(self.type_info.iter()) {
+            for i in 0..self.entities.len() {
+                unsafe { (ty.drop)(data.get(ty, i)) };
+            }
+        }
+        self.entities.clear();
+        self.rows.clear();
+    }
+
+    pub fn shrink_to_fit(&mut self) -> () {
+        let new_size = self.len();
+        if new_size == self.capacity {
+            return;
+        }
+        for (data, ty) in self.data.iter_mut().zip(self.type_info.iter()) {
+            unsafe { data.shrink(&ty.layout, self.capacity, new_size, &self.allocator) };
+        }
+        self.capacity = new_size;
+    }
+
+    pub fn column_of(&self, id: &ComponentId) -> Option<usize> {
+        return self.component_ids.binary_search(id).ok();
+    }
 
-        let index = index.unwrap();
+    pub fn contains(&self, id: &ComponentId) -> bool {
+        return self.mask.contains(*id);
+    }
+
+    pub fn contains_all(&self, ids: &ComponentSet) -> bool {
+        return self.mask.is_superset(ids);
+    }
+
+    pub fn type_info_of(&self, id: &ComponentId) -> Option<&TypeInfo> {
+        return Some(&self.type_info[self.column_of(id)?]);
+    }
+
+    pub fn type_info_of_mut(&mut self, id: &ComponentId) -> Option<&mut TypeInfo> {
+        return Some(&mut self.type_info[self.column_of(id)?]);
+    }
+
+    //removes the specified components and returns the data for the unspecefied ones
+    pub unsafe fn remove(&mut self, entity: Entity, ids: &[ComponentId]) -> Option<Box<[*mut u8]>> {
+        let index = self.index_of(entity)?;
+        let last = self.len() - 1;
 
-        self.entities.remove(index);
         //remove the all the components
         let mut ret = Vec::new();
-        for (data, ty_id) in self.data.iter().zip(self.type_ids.iter()) {
-            let ty = self.types[ty_id];
-            let last = self.capacity - 1;
-            let moved = data.get(&ty, last as usize);
-            if type_id.contains(ty_id) {
-                unsafe {
-                    let removed = data.get(&ty, index);
-                    (ty.drop)(removed);
-                    ptr::copy_nonoverlapping(moved, removed, ty.layout.size());
-                }
-                continue;
+        for ((data, ty), ty_id) in self
+            .data
+            .iter()
+            .zip(self.type_info.iter())
+            .zip(self.component_ids.iter())
+        {
+            let removed = data.get(ty, index);
+            if ids.contains(ty_id) {
+                (ty.drop)(removed);
+            } else {
+                let new = self.allocator.allocate(ty.layout).unwrap().as_ptr() as *mut u8;
+                ptr::copy_nonoverlapping(removed, new, ty.layout.size());
+                ret.push(new);
+            }
+            // fill the hole with the last row so the columns stay dense
+            if index != last {
+                ptr::copy_nonoverlapping(data.get(ty, last), removed, ty.layout.size());
             }
-            let new = self.allocator.allocate(ty.layout).unwrap().as_ptr() as *mut u8;
-            //let new = alloc(ty.layout);
-            ptr::copy_nonoverlapping(data.get(&ty, index), new, ty.layout.size());
-            ret.push(new);
         }
+        self.swap_remove(index);
         return Some(ret.into_boxed_slice());
     }
 
     pub unsafe fn remove_whole(&mut self, entity: Entity) -> Option<Box<[*mut u8]>> {
         //why?
         // don't do this we gonna get empty stuff
-        //return self.remove(entity, &self.type_ids.clone());
+        //return self.remove(entity, &self.component_ids.clone());
         return self.remove(entity, &[]);
     }
 
     pub fn destroy(&mut self, entity: Entity) -> () {
-        let index = self.entities.binary_search(&entity).ok();
-        if index.is_none() {
+        let Some(index) = self.index_of(entity) else {
             return;
+        };
+        self.destroy_index(index);
+    }
+
+    pub fn destroy_index(&mut self, index: usize) -> () {
+        for (data, ty) in self.data.iter().zip(self.type_info.iter()) {
+            unsafe { (ty.drop)(data.get(ty, index)) };
         }
-        let index = index.unwrap();
-        self.entities.remove(index);
-        //remove the all the components
-        for (data, ty) in self.data.iter().zip(self.type_ids.iter()) {
-            let ty = self.types[ty];
-            unsafe {
-                let removed = data.get(&ty, index);
-                (ty.drop)(removed);
-                let last = self.capacity - 1;
-                let moved = data.get(&ty, last as usize);
-                ptr::copy_nonoverlapping(moved, removed, ty.layout.size());
-                //dealloc(removed, ty.layout);
-                let removed = NonNull::new(removed).unwrap();
-                self.allocator.deallocate(removed, ty.layout);
+        unsafe { self.forget_index(index) };
+    }
+
+    /// Removes the row without dropping it, the values must have been moved out.
+    pub unsafe fn forget_index(&mut self, index: usize) -> () {
+        let last = self.len() - 1;
+        if index != last {
+            for (data, ty) in self.data.iter().zip(self.type_info.iter()) {
+                let moved = data.get(ty, last);
+                ptr::copy_nonoverlapping(moved, data.get(ty, index), ty.layout.size());
             }
         }
+        self.swap_remove(index);
     }
 
-    pub fn has(&self, entity: Entity) -> bool {
-        return self.entities.contains(&entity);
+    //the last entity takes the row of the removed one, like its values did
+    fn swap_remove(&mut self, index: usize) -> () {
+        let entity = self.entities.swap_remove(index);
+        self.rows.remove(&entity);
+        if let Some(moved) = self.entities.get(index) {
+            self.rows.insert(*moved, index);
+        }
     }
 
-    pub unsafe fn get(&self, entity: Entity, type_ids: &[TypeId]) -> Option<Box<[*mut u8]>> {
-        let index = self.entities.binary_search(&entity).ok()?;
-        let mut res = Vec::new();
-        for type_id in type_ids {
-            let ty_index_matcher = || {
-                for (i, t) in self.type_ids.iter().enumerate() {
-                    if *t == *type_id {
-                        return Some(i);
-                    }
-                }
-                None
-            };
+    pub fn index_of(&self, entity: Entity) -> Option<usize> {
+        return self.rows.get(&entity).copied();
+    }
 
-            let ty_index = ty_index_matcher()?;
+    pub fn has(&self, entity: Entity) -> bool {
+        return self.rows.contains_key(&entity);
+    }
 
-            let ty = &self.types[type_id];
+    pub unsafe fn get(&self, entity: Entity, ids: &[ComponentId]) -> Option<Box<[*mut u8]>> {
+        let index = self.index_of(entity)?;
+        let mut res = Vec::new();
+        for id in ids {
+            let ty_index = self.column_of(id)?;
+            let ty = &self.type_info[ty_index];
             res.push(self.data[ty_index].get(ty, index));
         }
         return Some(res.into_boxed_slice());
     }
 
-    pub unsafe fn get_by_index(&self, index: usize, type_ids: &[TypeId]) -> Option<Box<[*mut u8]>> {
+    pub unsafe fn get_by_index(&self, index: usize, ids: &[ComponentId]) -> Option<Box<[*mut u8]>> {
         let mut res = Vec::new();
-        for type_id in type_ids {
-            let ty_index_matcher = || {
-                for (i, t) in self.type_ids.iter().enumerate() {
-                    if *t == *type_id {
-                        return Some(i);
-                    }
-                }
-                None
-            };
-
-            let ty_index = ty_index_matcher()?;
-
-            let ty = &self.types[type_id];
+        for id in ids {
+            let ty_index = self.column_of(id)?;
+            let ty = &self.type_info[ty_index];
             res.push(self.data[ty_index].get(ty, index));
         }
         return Some(res.into_boxed_slice());
     }
 
-    pub unsafe fn get_by_type_index(
-        &self,
-        type_id: &TypeId,
-        ty_index: usize,
-        index: usize,
-    ) -> Option<NonNull<u8>> {
-        let ty = &self.types[type_id];
+    pub unsafe fn get_by_type_index(&self, ty_index: usize, index: usize) -> Option<NonNull<u8>> {
+        let ty = &self.type_info[ty_index];
         return unsafe {
             Some(NonNull::new(self.data[ty_index].get(ty, index)).expect("ptr is null"))
         };
     }
 
-    unsafe fn alloc(&self, id: TypeId, size: usize) -> ComponentData<A> {
-        let info = self.types.get(&id).expect("invalid type");
-        return ComponentData::new(info.layout, size, &self.allocator);
-    }
-
     //TODO: do this correctly
     unsafe fn grow(&mut self, new_size: usize) {
-        for (data, ty) in self.data.iter_mut().zip(self.type_ids.iter()) {
-            let ty = self.types[ty];
+        for (data, ty) in self.data.iter_mut().zip(self.type_info.iter()) {
             data.grow(&ty.layout, self.capacity, new_size, &self.allocator);
         }
         self.capacity = new_size;
@@ -212,10 +269,6 @@ where
     pub fn entity_iter(&self) -> impl Iterator<Item = &Entity> {
         return self.entities.iter();
     }
-
-    pub fn entity_iter_mut(&mut self) -> impl Iterator<Item = &mut Entity> {
-        return self.entities.iter_mut();
-    }
 }
 
 impl<A> Drop for Archetype<A>
@@ -223,10 +276,8 @@ where
     A: Allocator,
 {
     fn drop(&mut self) {
-        for (data, ty) in self.data.iter().zip(self.type_ids.iter()) {
-            let ty = &self.types[ty];
-
-            for i in 0..self.capacity {
+        for (data, ty) in self.data.iter().zip(self.type_info.iter()) {
+            for i in 0..self.len() {
                 unsafe {
                     let ptr = data.get(ty, i);
                     (ty.drop)(ptr);
@@ -246,7 +297,9 @@ where
 
 #[derive(Eq, Hash, PartialEq, Clone, Copy, Debug)]
 pub struct TypeInfo {
-    id: TypeId,
+    type_id: Option<TypeId>,
+    name: &'static str,
+    storage: StorageType,
     layout: Layout,
     array_layout: Layout,
     drop: unsafe fn(*mut u8) -> (),
@@ -258,13 +311,74 @@ impl TypeInfo {
             ptr.cast::<T>().drop_in_place();
         }
         Self {
-            id: TypeId::of::<T>(),
+            type_id: Some(TypeId::of::<T>()),
+            name: core::any::type_name::<T>(),
+            storage: StorageType::Table,
             layout: Layout::new::<T>(),
             array_layout: Layout::new::<T>(),
             drop: drop_ptr::<T>,
         }
     }
 
+    //like new but with the metadata the component declares, if it is one
+    pub fn of<T: 'static>() -> Self {
+        Self {
+            name: <T as Metadata>::name(),
+            storage: <T as Metadata>::STORAGE,
+            ..Self::new::<T>()
+        }
+    }
+
+    //a relationship pair stores a `T` but isn't the component `T` itself
+    pub fn pair<T: Component>() -> Self {
+        return Self::of::<T>().as_pair();
+    }
+
+    //the pair of the relation this describes
+    pub fn as_pair(self) -> Self {
+        Self {
+            type_id: None,
+            storage: StorageType::Table,
+            ..self
+        }
+    }
+
+    /// Metadata for a component without a rust type, `drop` runs on every
+    /// value that gets removed.
+    pub fn dynamic(
+        name: &'static str,
+        layout: Layout,
+        drop: Option<unsafe fn(*mut u8) -> ()>,
+    ) -> Self {
+        unsafe fn forget(_: *mut u8) -> () {}
+        Self {
+            type_id: None,
+            name,
+            storage: StorageType::Table,
+            layout,
+            array_layout: layout,
+            drop: drop.unwrap_or(forget),
+        }
+    }
+
+    //none for components that were defined at runtime
+    pub fn type_id(&self) -> Option<TypeId> {
+        return self.type_id;
+    }
+
+    //the rust type name unless the component picked its own
+    pub fn name(&self) -> &'static str {
+        return self.name;
+    }
+
+    pub fn storage(&self) -> StorageType {
+        return self.storage;
+    }
+
+    pub fn layout(&self) -> Layout {
+        return self.layout;
+    }
+
     pub unsafe fn drop(&self, ptr: *mut u8) -> () {
         (self.drop)(ptr)
     }
@@ -333,6 +447,24 @@ where
         };
     }
 
+    pub unsafe fn shrink(
+        &mut self,
+        layout: &Layout,
+        old_size: usize,
+        new_size: usize,
+        allocator: &A,
+    ) -> () {
+        let old_layout = Layout::from_size_align(layout.size() * old_size, layout.align())
+            .expect("way to many components");
+        let new_layout = Layout::from_size_align(layout.size() * new_size, layout.align())
+            .expect("failed to shrink component storage");
+        let ptr = allocator
+            .shrink(self.0, old_layout, new_layout)
+            .unwrap()
+            .as_ptr() as *mut u8;
+        self.0 = NonNull::new(ptr).expect("expected a valid pointer,got a null pointer instead");
+    }
+
     pub unsafe fn as_slice(&mut self, size: usize) -> &[*mut u8] {
         let refs = &self.0.as_ptr();
         let ptr = refs as *const *mut u8;
@@ -345,11 +477,20 @@ where
     }
 }
 
+/// Every archetype of a world, kept in the order they were created in.
+///
+/// Lookups and queries visit archetypes in that order, so two worlds that went
+/// through the same operations iterate the same way.
 pub struct ArchetypeSet<A>
 where
     A: Allocator,
 {
-    archetypes: FxHashMap<Box<[TypeId]>, Archetype<A>>,
+    archetypes: Vec<Archetype<A>>,
+    //position in `archetypes` by sorted component ids
+    index: FxHashMap<Box<[ComponentId]>, usize>,
+    //position in `archetypes` of the archetype every entity is in, the row is
+    //kept by the archetype itself
+    locations: FxHashMap<Entity, usize>,
 }
 
 impl<A> ArchetypeSet<A>
@@ -358,77 +499,192 @@ where
 {
     pub fn new() -> Self {
         Self {
-            archetypes: FxHashMap::default(),
+            archetypes: Vec::new(),
+            index: FxHashMap::default(),
+            locations: FxHashMap::default(),
+        }
+    }
+
+    pub fn has(&self, types: &[ComponentId]) -> bool {
+        return self.index.contains_key(types);
+    }
+
+    pub fn add(&mut self, types: &[ComponentId], type_info: &[TypeInfo], allocator: A) -> () {
+        let archetype = Archetype::new(types, type_info, allocator);
+        match self.index.get(types) {
+            Some(i) => self.archetypes[*i] = archetype,
+            None => {
+                self.index.insert(types.into(), self.archetypes.len());
+                self.archetypes.push(archetype);
+            }
+        }
+    }
+
+    pub fn remove(&mut self, types: &[ComponentId]) -> () {
+        if let Some(i) = self.index.remove(types) {
+            self.archetypes.remove(i);
+            self.reindex();
+        }
+    }
+
+    pub fn get_or_add(
+        &mut self,
+        types: &[ComponentId],
+        type_info: &[TypeInfo],
+        allocator: A,
+    ) -> &mut Archetype<A> {
+        let i = self.position_or_add(types, type_info, allocator);
+        return &mut self.archetypes[i];
+    }
+
+    fn position_or_add(
+        &mut self,
+        types: &[ComponentId],
+        type_info: &[TypeInfo],
+        allocator: A,
+    ) -> usize {
+        if !self.has(types) {
+            self.add(types, type_info, allocator);
+        }
+        return self.index[types];
+    }
+
+    /// Adds `entity` to the archetype made up of `types`, see `Archetype::add`.
+    ///
+    /// # Safety
+    /// Same as `Archetype::add`.
+    pub unsafe fn add_entity(
+        &mut self,
+        types: &[ComponentId],
+        type_info: &[TypeInfo],
+        allocator: A,
+        entity: Entity,
+        data: &[*mut u8],
+    ) -> () {
+        let i = self.position_or_add(types, type_info, allocator);
+        self.archetypes[i].add(entity, data);
+        self.locations.insert(entity, i);
+    }
+
+    /// Adds rows for `entities` to the archetype made up of `types`, see
+    /// `Archetype::push_uninit`.
+    ///
+    /// # Safety
+    /// Same as `Archetype::push_uninit`.
+    pub unsafe fn push_uninit(
+        &mut self,
+        types: &[ComponentId],
+        type_info: &[TypeInfo],
+        allocator: A,
+        entities: &[Entity],
+    ) -> Vec<*mut u8> {
+        let i = self.position_or_add(types, type_info, allocator);
+        self.locations.extend(entities.iter().map(|x| (*x, i)));
+        return self.archetypes[i].push_uninit(entities);
+    }
+
+    //drops the row of `entity`
+    pub fn destroy(&mut self, entity: Entity) -> () {
+        if let Some(archetype) = self.get_by_entity_mut(entity) {
+            archetype.destroy(entity);
         }
+        self.locations.remove(&entity);
     }
 
-    pub fn has(&self, types: &[TypeId]) -> bool {
-        return self.archetypes.contains_key(types);
+    /// Removes the row of `entity` without dropping it, the values must have been
+    /// moved out.
+    pub unsafe fn forget(&mut self, entity: Entity) -> () {
+        if let Some(archetype) = self.get_by_entity_mut(entity) {
+            archetype.forget_index(archetype.index_of(entity).unwrap());
+        }
+        self.locations.remove(&entity);
     }
 
-    pub fn add(&mut self, types: &[TypeId], type_info: &[TypeInfo], allocator: A) -> () {
-        self.archetypes
-            .insert(types.into(), Archetype::new(types, type_info, allocator));
+    //drops every entity but keeps the archetypes around for reuse
+    pub fn clear(&mut self) -> () {
+        self.archetypes.iter_mut().for_each(|x| x.clear());
+        self.locations.clear();
     }
 
-    pub fn remove(&mut self, types: &[TypeId]) -> () {
-        self.archetypes.remove(types);
+    //keeps the order of the archetypes that stay, `f` may remove entities too
+    pub fn retain(&mut self, f: impl FnMut(&mut Archetype<A>) -> bool) -> () {
+        self.archetypes.retain_mut(f);
+        self.reindex();
     }
 
-    pub fn get(&self, types: &[TypeId]) -> Option<&Archetype<A>> {
-        return self.archetypes.get(types);
+    fn reindex(&mut self) -> () {
+        self.index = self
+            .archetypes
+            .iter()
+            .enumerate()
+            .map(|(i, x)| (x.component_ids.clone(), i))
+            .collect();
+        self.locations = self
+            .archetypes
+            .iter()
+            .enumerate()
+            .flat_map(|(i, x)| x.entity_iter().map(move |x| (*x, i)))
+            .collect();
     }
 
-    pub fn get_mut(&mut self, types: &[TypeId]) -> Option<&mut Archetype<A>> {
-        return self.archetypes.get_mut(types);
+    pub fn get(&self, types: &[ComponentId]) -> Option<&Archetype<A>> {
+        return Some(&self.archetypes[*self.index.get(types)?]);
+    }
+
+    pub fn get_mut(&mut self, types: &[ComponentId]) -> Option<&mut Archetype<A>> {
+        return Some(&mut self.archetypes[*self.index.get(types)?]);
     }
 
     pub fn get_by_entity(&self, entity: Entity) -> Option<&Archetype<A>> {
-        return self.archetypes.values().filter(|x| x.has(entity)).nth(0);
+        let archetype = &self.archetypes[*self.locations.get(&entity)?];
+        return archetype.has(entity).then_some(archetype);
     }
 
     pub fn get_by_entity_mut(&mut self, entity: Entity) -> Option<&mut Archetype<A>> {
-        return self
-            .archetypes
-            .values_mut()
-            .filter(|x| x.has(entity))
-            .nth(0);
+        let archetype = &mut self.archetypes[*self.locations.get(&entity)?];
+        return archetype.has(entity).then_some(archetype);
     }
 
-    pub fn get_similiar(&self, types: &[TypeId]) -> Option<Box<[&Archetype<A>]>> {
+    //in creation order
+    pub fn get_similiar(&self, types: &[ComponentId]) -> Option<Box<[&Archetype<A>]>> {
+        let mask = ComponentSet::from_ids(types);
         let similiar = self
             .archetypes
-            .values()
-            .filter(|x| x.type_ids.iter().any(|y| types.contains(y)))
+            .iter()
+            .filter(|x| x.contains_all(&mask))
             .collect::<Vec<_>>();
         return Some(similiar.into_boxed_slice());
     }
 
-    pub fn get_similiar_mut(&mut self, types: &[TypeId]) -> Option<Box<[&mut Archetype<A>]>> {
+    pub fn get_similiar_mut(&mut self, types: &[ComponentId]) -> Option<Box<[&mut Archetype<A>]>> {
+        let mask = ComponentSet::from_ids(types);
         let similiar = self
             .archetypes
-            .values_mut()
-            .filter(|x| x.type_ids.iter().any(|y| types.contains(y)))
+            .iter_mut()
+            .filter(|x| x.contains_all(&mask))
             .collect::<Vec<_>>();
         return Some(similiar.into_boxed_slice());
     }
 
-    pub fn iter(&mut self) -> impl Iterator<Item = &Archetype<A>> {
-        return self.archetypes.values();
+    pub fn iter(&self) -> impl Iterator<Item = &Archetype<A>> {
+        return self.archetypes.iter();
     }
 
     pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Archetype<A>> {
-        return self.archetypes.values_mut();
+        return self.archetypes.iter_mut();
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Archetype, ComponentData, TypeInfo};
-    use crate::{bundle::Bundle, entity::Entity};
+    use super::{Archetype, ArchetypeSet, ComponentData, TypeInfo};
+    use crate::{
+        bundle::Bundle,
+        component::{Component, ComponentId},
+        entity::Entity,
+    };
     use std::{
         alloc::{dealloc, Global, Layout},
-        any::TypeId,
         assert_eq,
         mem::{align_of, size_of},
         vec::Vec,
@@ -440,32 +696,28 @@ mod tests {
         b: u32,
     }
 
+    impl Component for TestComponent {}
+
     #[test]
     fn archetype_create() {
-        let type_ids = [
-            TypeId::of::<u32>(),
-            TypeId::of::<f64>(),
-            TypeId::of::<TestComponent>(),
-        ];
+        let type_ids = [ComponentId(0), ComponentId(1), ComponentId(2)];
         let type_infos = [
             TypeInfo::new::<u32>(),
             TypeInfo::new::<f64>(),
             TypeInfo::new::<TestComponent>(),
         ];
         let archetype = Archetype::<Global>::new(&type_ids, &type_infos, Global);
-        assert_eq!(*archetype.type_ids, type_ids);
-        assert!(archetype.types.contains_key(&type_ids[0]));
-        assert!(archetype.types.contains_key(&type_ids[1]));
-        assert!(archetype.types.contains_key(&type_ids[2]));
+        assert_eq!(*archetype.component_ids, type_ids);
+        assert!(archetype.contains(&type_ids[0]));
+        assert!(archetype.contains(&type_ids[1]));
+        assert!(archetype.contains(&type_ids[2]));
+        assert!(!archetype.contains(&ComponentId(3)));
+        assert_eq!(archetype.type_info_of(&type_ids[1]), Some(&type_infos[1]));
     }
 
     #[test]
     fn archetype_add() {
-        let type_ids = [
-            TypeId::of::<u32>(),
-            TypeId::of::<u64>(),
-            TypeId::of::<TestComponent>(),
-        ];
+        let type_ids = [ComponentId(0), ComponentId(1), ComponentId(2)];
         let type_infos = [
             TypeInfo::new::<u32>(),
             TypeInfo::new::<u64>(),
@@ -492,11 +744,7 @@ mod tests {
 
     #[test]
     fn archetype_remove() {
-        let type_ids = [
-            TypeId::of::<u32>(),
-            TypeId::of::<u64>(),
-            TypeId::of::<TestComponent>(),
-        ];
+        let type_ids = [ComponentId(0), ComponentId(1), ComponentId(2)];
         let type_infos = [
             TypeInfo::new::<u32>(),
             TypeInfo::new::<u64>(),
@@ -526,11 +774,7 @@ mod tests {
 
     #[test]
     fn archetype_remove_whole() {
-        let type_ids = [
-            TypeId::of::<u32>(),
-            TypeId::of::<u64>(),
-            TypeId::of::<TestComponent>(),
-        ];
+        let type_ids = [ComponentId(0), ComponentId(1), ComponentId(2)];
         let type_infos = [
             TypeInfo::new::<u32>(),
             TypeInfo::new::<u64>(),
@@ -586,11 +830,7 @@ mod tests {
 
     #[test]
     fn archetype_add_many() {
-        let type_ids = [
-            TypeId::of::<u32>(),
-            TypeId::of::<u64>(),
-            TypeId::of::<TestComponent>(),
-        ];
+        let type_ids = [ComponentId(0), ComponentId(1), ComponentId(2)];
         let type_infos = [
             TypeInfo::new::<u32>(),
             TypeInfo::new::<u64>(),
@@ -605,16 +845,139 @@ mod tests {
         }
     }
 
+    #[test]
+    fn archetype_shrink_to_fit() {
+        let type_ids = [ComponentId(0), ComponentId(1)];
+        let type_infos = [TypeInfo::new::<u32>(), TypeInfo::new::<TestComponent>()];
+        let mut archetype = Archetype::<Global>::new(&type_ids, &type_infos, Global);
+
+        for i in 0..40 {
+            let mut test_data = (i, TestComponent { a: 1, b: i });
+            unsafe { archetype.add(Entity(i as u64), &test_data.as_ptrs()) };
+        }
+        for i in 0..35 {
+            archetype.destroy(Entity(i));
+        }
+        archetype.shrink_to_fit();
+        assert_eq!(archetype.capacity(), 5);
+
+        for i in 35..40 {
+            let data = unsafe { archetype.get(Entity(i), &type_ids).unwrap() };
+            unsafe {
+                assert_eq!(*(data[0] as *mut u32), i as u32);
+                assert_eq!(
+                    *(data[1] as *mut TestComponent),
+                    TestComponent { a: 1, b: i as u32 }
+                );
+            }
+        }
+
+        archetype.clear();
+        archetype.shrink_to_fit();
+        assert_eq!(archetype.capacity(), 0);
+        let mut test_data = (3u32, TestComponent { a: 1, b: 2 });
+        unsafe { archetype.add(Entity(50), &test_data.as_ptrs()) };
+        assert_eq!(archetype.len(), 1);
+    }
+
+    #[test]
+    fn archetype_set_similiar() {
+        let mut set = ArchetypeSet::<Global>::new();
+        let info = [TypeInfo::new::<u32>(), TypeInfo::new::<u64>()];
+        set.add(&[ComponentId(1), ComponentId(70)], &info, Global);
+        set.add(&[ComponentId(1), ComponentId(2)], &info, Global);
+        set.add(&[ComponentId(70)], &info[..1], Global);
+
+        assert_eq!(set.get_similiar(&[ComponentId(1)]).unwrap().len(), 2);
+        assert_eq!(set.get_similiar(&[ComponentId(70)]).unwrap().len(), 2);
+        assert_eq!(
+            set.get_similiar(&[ComponentId(70), ComponentId(1)])
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(set.get_similiar(&[ComponentId(130)]).unwrap().len(), 0);
+        assert_eq!(set.get_similiar(&[]).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn archetype_set_order() {
+        let mut set = ArchetypeSet::<Global>::new();
+        let info = [TypeInfo::new::<u32>(), TypeInfo::new::<u64>()];
+        let types: [&[ComponentId]; 4] = [
+            &[ComponentId(9)],
+            &[ComponentId(1), ComponentId(9)],
+            &[ComponentId(3)],
+            &[ComponentId(2), ComponentId(9)],
+        ];
+        for x in types {
+            set.get_or_add(x, &info[..x.len()], Global);
+        }
+        let order = |set: &ArchetypeSet<Global>, ids: &[ComponentId]| {
+            return set
+                .get_similiar(ids)
+                .unwrap()
+                .iter()
+                .map(|x| x.component_ids.clone())
+                .collect::<Vec<_>>();
+        };
+        assert_eq!(
+            order(&set, &[ComponentId(9)]),
+            [types[0].into(), types[1].into(), types[3].into()]
+        );
+
+        // removing one keeps the order of the others
+        set.retain(|x| x.component_ids[..] != *types[1]);
+        set.get_or_add(types[1], &info, Global);
+        assert_eq!(
+            order(&set, &[ComponentId(9)]),
+            [types[0].into(), types[3].into(), types[1].into()]
+        );
+        assert_eq!(set.get(types[2]).unwrap().component_ids[..], *types[2]);
+    }
+
+    #[test]
+    fn archetype_set_locations() {
+        let mut set = ArchetypeSet::<Global>::new();
+        let info = [TypeInfo::new::<u32>()];
+        let (a, b) = ([ComponentId(0)], [ComponentId(1)]);
+        for i in 0..4 {
+            let mut value = (i as u32,);
+            unsafe { set.add_entity(&a, &info, Global, Entity(i), &value.as_ptrs()) };
+        }
+
+        // the last row fills the hole
+        set.destroy(Entity(1));
+        let archetype = set.get_by_entity(Entity(3)).unwrap();
+        assert_eq!(archetype.index_of(Entity(3)), Some(1));
+        assert!(set.get_by_entity(Entity(1)).is_none());
+
+        // moving to another archetype
+        unsafe {
+            let values = set
+                .get_by_entity_mut(Entity(0))
+                .unwrap()
+                .remove(Entity(0), &[]);
+            set.add_entity(&b, &info, Global, Entity(0), &values.unwrap());
+        }
+        assert_eq!(set.get_by_entity(Entity(0)).unwrap().component_ids[..], b);
+        assert_eq!(
+            set.get_by_entity(Entity(2)).unwrap().index_of(Entity(2)),
+            Some(0)
+        );
+
+        // dropping an archetype moves the ones after it
+        set.remove(&a);
+        assert!(set.get_by_entity(Entity(2)).is_none());
+        assert_eq!(set.get_by_entity(Entity(0)).unwrap().component_ids[..], b);
+    }
+
     #[test]
     fn archetype_get() {}
 
     #[test]
     fn archetype_drop() {
-        let type_ids = [
-            TypeId::of::<u32>(),
-            TypeId::of::<u64>(),
-            TypeId::of::<TestComponent>(),
-        ];
+        let type_ids = [ComponentId(0), ComponentId(1), ComponentId(2)];
         let type_infos = [
             TypeInfo::new::<u32>(),
             TypeInfo::new::<u64>(),
@@ -1,7 +1,11 @@
-use alloc::boxed::Box;
+use alloc::{boxed::Box, vec::Vec};
 use core::any::TypeId;
 
-use crate::archetype::TypeInfo;
+use crate::{
+    archetype::TypeInfo,
+    component::{Component, ComponentInfo},
+    world::WorldAllocator,
+};
 
 pub fn into_ptr<T>(data: &mut T) -> *mut u8 {
     return data as *const _ as *mut u8;
@@ -15,18 +19,95 @@ pub fn from_ptr_mut<'a, T>(data: *mut u8) -> &'a mut T {
     unsafe { &mut *(data as *const T as *mut T) }
 }
 
+//panics if a component shows up twice, used by bundles made up of other bundles
+pub fn assert_unique(bundle: &str, type_info: &[TypeInfo]) -> () {
+    for (i, ty) in type_info.iter().enumerate() {
+        if type_info[..i].iter().any(|x| x.type_id() == ty.type_id()) {
+            panic!(
+                "bundle `{}` contains the component `{}` more than once",
+                bundle,
+                ty.name()
+            );
+        }
+    }
+}
+
 pub trait Bundle<'a> {
     type Target;
     type TargetMut;
+    //how many components the bundle is made of
+    const COUNT: usize;
     fn type_info() -> Box<[TypeInfo]>;
     fn type_ids() -> Box<[TypeId]>;
+    //the metadata and hooks a world registers the components with when it first sees them
+    fn component_info<A: WorldAllocator>() -> Vec<ComponentInfo<A>>;
     unsafe fn as_ptrs(&mut self) -> Box<[*mut u8]>;
     unsafe fn from_ptr(data: &[*mut u8]) -> Self::Target;
     unsafe fn from_ptr_mut(data: &[*mut u8]) -> Self::TargetMut;
 }
 
+impl<'a, C> Bundle<'a> for C
+where
+    C: Component,
+{
+    type Target = &'a C;
+    type TargetMut = &'a mut C;
+    const COUNT: usize = 1;
+
+    fn type_info() -> Box<[TypeInfo]> {
+        Box::new([TypeInfo::of::<C>()])
+    }
+
+    fn type_ids() -> Box<[TypeId]> {
+        Box::new([TypeId::of::<C>()])
+    }
+
+    fn component_info<A: WorldAllocator>() -> Vec<ComponentInfo<A>> {
+        Vec::from([ComponentInfo::new::<C>()])
+    }
+
+    unsafe fn as_ptrs(&mut self) -> Box<[*mut u8]> {
+        Box::new([into_ptr(self)])
+    }
+
+    unsafe fn from_ptr(data: &[*mut u8]) -> Self::Target {
+        from_ptr(data[0])
+    }
+
+    unsafe fn from_ptr_mut(data: &[*mut u8]) -> Self::TargetMut {
+        from_ptr_mut(data[0])
+    }
+}
+
+// the bundle without components, matches every entity
+impl<'a> Bundle<'a> for () {
+    type Target = ();
+    type TargetMut = ();
+    const COUNT: usize = 0;
+
+    fn type_info() -> Box<[TypeInfo]> {
+        Box::new([])
+    }
+
+    fn type_ids() -> Box<[TypeId]> {
+        Box::new([])
+    }
+
+    fn component_info<A: WorldAllocator>() -> Vec<ComponentInfo<A>> {
+        Vec::new()
+    }
+
+    unsafe fn as_ptrs(&mut self) -> Box<[*mut u8]> {
+        Box::new([])
+    }
+
+    unsafe fn from_ptr(_: &[*mut u8]) -> Self::Target {}
+
+    unsafe fn from_ptr_mut(_: &[*mut u8]) -> Self::TargetMut {}
+}
+
 macro_rules! impl_bundle {
-    ($($T:ident $I:tt),*)//; $($I:ident),*)
+    ($($T:ident $I:tt),*)
     => {
         impl<'a,$($T),*> Bundle<'a> for ($($T,)*)
             where $($T: 'static ),*
@@ -38,9 +119,11 @@ macro_rules! impl_bundle {
 			#[allow(unused_parens)]
             type TargetMut = ($(&'a mut $T),*);
 
+            const COUNT: usize = [$($I),*].len();
+
             fn type_info() -> Box<[TypeInfo]> {
                 Box::new([
-                         $(TypeInfo::new::<$T>()),*
+                         $(TypeInfo::of::<$T>()),*
                 ])
             }
 
@@ -50,9 +133,14 @@ macro_rules! impl_bundle {
                 ])
             }
 
+			fn component_info<A: WorldAllocator>() -> Vec<ComponentInfo<A>> {
+                Vec::from([
+                         $(ComponentInfo::new::<$T>()),*
+                ])
+            }
+
 			unsafe fn as_ptrs(&mut self) -> Box<[*mut u8]> {
                 Box::new( [
-                    //$(into_ptr(&mut self.$I)),*
                     $(into_ptr(&mut self.$I)),*
                 ])
             }
@@ -0,0 +1,82 @@
+use alloc::boxed::Box;
+use core::alloc::Allocator;
+
+use crate::{
+    bundle::Bundle,
+    entity::Entity,
+    observer::Event,
+    world::{World, WorldAllocator},
+};
+
+//a change queued by `Commands`
+pub(crate) type Command<A> = Box<dyn FnOnce(&mut World<A>) -> ()>;
+
+/// Records structural changes while the world can't make them, like inside
+/// hooks and observers.
+///
+/// The changes are applied in order once the operation that handed out the
+/// `Commands` is done.
+pub struct Commands<'w, A>
+where
+    A: Allocator,
+{
+    world: &'w mut World<A>,
+}
+
+impl<'w, A> Commands<'w, A>
+where
+    A: WorldAllocator,
+{
+    pub(crate) fn new(world: &'w mut World<A>) -> Self {
+        Self { world }
+    }
+
+    pub fn push<F>(&mut self, command: F) -> ()
+    where
+        F: FnOnce(&mut World<A>) -> () + 'static,
+    {
+        self.world.deferred.push_back(Box::new(command));
+    }
+
+    //the entity exists right away, its components show up with the other changes
+    pub fn spawn<T>(&mut self, bundle: T) -> Entity
+    where
+        T: Bundle<'static> + 'static,
+    {
+        let entity = self.world.spawn();
+        self.add(entity, bundle);
+        return entity;
+    }
+
+    pub fn add<T>(&mut self, entity: Entity, bundle: T) -> ()
+    where
+        T: Bundle<'static> + 'static,
+    {
+        self.push(move |world| world.add(entity, bundle));
+    }
+
+    pub fn remove<T>(&mut self, entity: Entity) -> ()
+    where
+        T: Bundle<'static> + 'static,
+    {
+        self.push(move |world| {
+            world.remove::<T>(entity);
+        });
+    }
+
+    pub fn destroy(&mut self, entity: Entity) -> () {
+        self.push(move |world| world.destroy(entity));
+    }
+
+    pub fn despawn_recursive(&mut self, entity: Entity) -> () {
+        self.push(move |world| world.despawn_recursive(entity));
+    }
+
+    pub fn trigger<E>(&mut self, event: E, entities: &[Entity]) -> ()
+    where
+        E: Event,
+    {
+        let entities = entities.to_vec();
+        self.push(move |world| world.trigger(event, &entities));
+    }
+}
@@ -0,0 +1,242 @@
+use alloc::{boxed::Box, rc::Rc, string::String, sync::Arc, vec::Vec};
+use core::{alloc::Allocator, any::type_name};
+
+use crate::{
+    archetype::TypeInfo,
+    entity::Entity,
+    world::{DeferredWorld, WorldAllocator},
+};
+
+#[derive(Eq, Hash, PartialEq, Clone, Copy, Debug, Default)]
+pub enum StorageType {
+    #[default]
+    Table,
+    Sparse,
+}
+
+/// Identifies a component inside a single world.
+///
+/// Handed out in the order components are first seen, so they can be used
+/// as an index.
+#[derive(Eq, Hash, PartialEq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub struct ComponentId(pub u32);
+
+impl ComponentId {
+    pub fn index(&self) -> usize {
+        return self.0 as usize;
+    }
+}
+
+/// Bitset of component ids, lets archetypes be matched against a query
+/// without looking up every component.
+#[derive(Eq, Hash, PartialEq, Clone, Debug, Default)]
+pub struct ComponentSet(Vec<u64>);
+
+impl ComponentSet {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn from_ids(ids: &[ComponentId]) -> Self {
+        let mut set = Self::new();
+        ids.iter().for_each(|x| set.insert(*x));
+        return set;
+    }
+
+    pub fn insert(&mut self, id: ComponentId) -> () {
+        let (word, bit) = (id.index() / 64, id.index() % 64);
+        if word >= self.0.len() {
+            self.0.resize(word + 1, 0);
+        }
+        self.0[word] |= 1 << bit;
+    }
+
+    pub fn contains(&self, id: ComponentId) -> bool {
+        let (word, bit) = (id.index() / 64, id.index() % 64);
+        return self.0.get(word).is_some_and(|x| x & (1 << bit) != 0);
+    }
+
+    //every id in `other` is in `self` as well
+    pub fn is_superset(&self, other: &ComponentSet) -> bool {
+        return other
+            .0
+            .iter()
+            .enumerate()
+            .all(|(i, x)| self.0.get(i).copied().unwrap_or(0) & x == *x);
+    }
+}
+
+/// Runs when a component is added to, inserted into or removed from `Entity`.
+///
+/// Removal hooks run while the component is still there.
+pub type ComponentHook<A> = fn(&mut DeferredWorld<A>, Entity) -> ();
+
+pub struct ComponentHooks<A>
+where
+    A: Allocator,
+{
+    pub on_add: Option<ComponentHook<A>>,
+    pub on_insert: Option<ComponentHook<A>>,
+    pub on_remove: Option<ComponentHook<A>>,
+}
+
+impl<A> ComponentHooks<A>
+where
+    A: Allocator,
+{
+    pub fn new() -> Self {
+        Self {
+            on_add: None,
+            on_insert: None,
+            on_remove: None,
+        }
+    }
+
+    pub fn on_add(&mut self, hook: ComponentHook<A>) -> &mut Self {
+        self.on_add = Some(hook);
+        return self;
+    }
+
+    pub fn on_insert(&mut self, hook: ComponentHook<A>) -> &mut Self {
+        self.on_insert = Some(hook);
+        return self;
+    }
+
+    pub fn on_remove(&mut self, hook: ComponentHook<A>) -> &mut Self {
+        self.on_remove = Some(hook);
+        return self;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.on_add.is_none() && self.on_insert.is_none() && self.on_remove.is_none();
+    }
+}
+
+impl<A> Default for ComponentHooks<A>
+where
+    A: Allocator,
+{
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+/// Per type metadata for components.
+///
+/// Every `'static` type can be used as a component inside a tuple bundle,
+/// implementing this trait (usually through `#[derive(Component)]`) lets the
+/// world know how to store it and which hooks to run, and makes it a bundle
+/// on its own.
+pub trait Component: 'static {
+    const STORAGE: StorageType = StorageType::Table;
+
+    fn name() -> &'static str {
+        return type_name::<Self>();
+    }
+
+    /// Sets up the hooks a world runs for this component.
+    ///
+    /// Called the first time a world sees the component through a typed api
+    /// like `spawn_with`, `add` or `register_component`. Components that only
+    /// ever arrive untyped, through deserialization or `transfer` into a world
+    /// that never saw them, get no hooks unless they are registered up front.
+    fn register_hooks<A>(_hooks: &mut ComponentHooks<A>) -> ()
+    where
+        A: WorldAllocator,
+    {
+    }
+}
+
+//the metadata of any `'static` type, what it declares if it is a `Component`
+//and the defaults otherwise
+pub(crate) trait Metadata: 'static {
+    const STORAGE: StorageType;
+
+    fn name() -> &'static str;
+
+    fn register_hooks<A>(hooks: &mut ComponentHooks<A>) -> ()
+    where
+        A: WorldAllocator;
+}
+
+impl<T> Metadata for T
+where
+    T: 'static,
+{
+    default const STORAGE: StorageType = StorageType::Table;
+
+    default fn name() -> &'static str {
+        return type_name::<T>();
+    }
+
+    default fn register_hooks<A>(_hooks: &mut ComponentHooks<A>) -> ()
+    where
+        A: WorldAllocator,
+    {
+    }
+}
+
+impl<T> Metadata for T
+where
+    T: Component,
+{
+    const STORAGE: StorageType = <T as Component>::STORAGE;
+
+    fn name() -> &'static str {
+        return <T as Component>::name();
+    }
+
+    fn register_hooks<A>(hooks: &mut ComponentHooks<A>) -> ()
+    where
+        A: WorldAllocator,
+    {
+        <T as Component>::register_hooks(hooks);
+    }
+}
+
+macro_rules! impl_component {
+    ($($T:ty),*) => {
+        $(impl Component for $T {})*
+    };
+}
+
+impl_component!(u8, u16, u32, u64, u128, usize);
+impl_component!(i8, i16, i32, i64, i128, isize);
+impl_component!(f32, f64, bool, char, String, &'static str);
+
+impl<T: 'static, const N: usize> Component for [T; N] {}
+impl<T: 'static> Component for Option<T> {}
+impl<T: 'static> Component for Vec<T> {}
+impl<T: ?Sized + 'static> Component for Box<T> {}
+impl<T: ?Sized + 'static> Component for Rc<T> {}
+impl<T: ?Sized + 'static> Component for Arc<T> {}
+
+pub struct ComponentInfo<A>
+where
+    A: Allocator,
+{
+    pub type_info: TypeInfo,
+    pub hooks: ComponentHooks<A>,
+}
+
+impl<A> ComponentInfo<A>
+where
+    A: WorldAllocator,
+{
+    pub fn new<T: 'static>() -> Self {
+        let mut hooks = ComponentHooks::new();
+        <T as Metadata>::register_hooks(&mut hooks);
+        Self {
+            type_info: TypeInfo::of::<T>(),
+            hooks,
+        }
+    }
+
+    //a component without hooks
+    pub fn dynamic(type_info: TypeInfo) -> Self {
+        Self {
+            type_info,
+            hooks: ComponentHooks::new(),
+        }
+    }
+}
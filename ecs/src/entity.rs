@@ -1,2 +1,16 @@
-#[derive(Eq, Hash, PartialEq, PartialOrd, Ord,Clone, Copy,Debug)]
+use rustc_hash::FxHashMap;
+
+#[derive(Eq, Hash, PartialEq, PartialOrd, Ord, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Entity(pub u64);
+
+/// Where entities ended up after being copied or moved into another world.
+pub type EntityMap = FxHashMap<Entity, Entity>;
+
+/// Components that reference other entities.
+///
+/// Used to point those references at the new entities when entities get
+/// copied into another world.
+pub trait MapEntities {
+    fn map_entities(&mut self, map: &mut dyn FnMut(Entity) -> Entity) -> ();
+}
@@ -0,0 +1,374 @@
+use alloc::{collections::VecDeque, vec::Vec};
+use core::{mem, ops::Deref};
+
+use rustc_hash::FxHashSet;
+use smallvec::SmallVec;
+
+use crate::{
+    component::{Component, ComponentId},
+    entity::{Entity, MapEntities},
+    world::{World, WorldAllocator},
+};
+
+/// The entity this one is a child of.
+///
+/// Only the world makes one, through `World::set_parent` and friends, so it
+/// always agrees with the `Children` of the parent. Removing it with
+/// `World::remove` detaches the entity like `World::remove_parent` does.
+#[derive(Eq, PartialEq, Debug)]
+pub struct Parent(Entity);
+
+impl Parent {
+    pub fn get(&self) -> Entity {
+        return self.0;
+    }
+}
+
+impl Component for Parent {
+    fn name() -> &'static str {
+        return "Parent";
+    }
+}
+
+impl MapEntities for Parent {
+    fn map_entities(&mut self, map: &mut dyn FnMut(Entity) -> Entity) -> () {
+        self.0 = map(self.0);
+    }
+}
+
+/// The children of an entity in the order they were added.
+///
+/// Like `Parent` only the world makes one, removing it detaches every child.
+#[derive(Eq, PartialEq, Debug)]
+pub struct Children(SmallVec<[Entity; 8]>);
+
+impl Deref for Children {
+    type Target = [Entity];
+
+    fn deref(&self) -> &[Entity] {
+        return &self.0;
+    }
+}
+
+impl Component for Children {
+    fn name() -> &'static str {
+        return "Children";
+    }
+}
+
+impl MapEntities for Children {
+    fn map_entities(&mut self, map: &mut dyn FnMut(Entity) -> Entity) -> () {
+        self.0.iter_mut().for_each(|x| *x = map(*x));
+    }
+}
+
+//clones the `Children` behind `src` into `dst`, outside this module they can't be cloned
+pub(crate) unsafe fn clone_children(src: *const u8, dst: *mut u8) -> () {
+    let children = &*src.cast::<Children>();
+    dst.cast::<Children>().write(Children(children.0.clone()));
+}
+
+impl<A> World<A>
+where
+    A: WorldAllocator,
+{
+    /// Makes `child` a child of `parent`, taking it away from its old parent.
+    ///
+    /// Panics if `parent` is `child` itself or one of its descendants.
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) -> () {
+        assert!(
+            child != parent && !self.ancestors(parent).any(|x| x == child),
+            "making {:?} a child of {:?} would create a cycle",
+            child,
+            parent
+        );
+        if self.parent(child) == Some(parent) {
+            return;
+        }
+        self.remove_parent(child);
+        match self.get_mut::<Children>(parent) {
+            Some(children) => children.0.push(child),
+            None => self.add(parent, Children(SmallVec::from_slice(&[child]))),
+        }
+        self.add(child, Parent(parent));
+        self.hierarchy_changed(child);
+    }
+
+    pub fn add_child(&mut self, parent: Entity, child: Entity) -> () {
+        self.set_parent(child, parent);
+    }
+
+    //detaches `child` from its parent and returns the parent it had
+    pub fn remove_parent(&mut self, child: Entity) -> Option<Entity> {
+        let parent = self.parent(child)?;
+        // the other side is taken care of by `unlink`
+        self.remove::<Parent>(child);
+        return Some(parent);
+    }
+
+    pub fn parent(&self, entity: Entity) -> Option<Entity> {
+        return Some(self.get::<Parent>(entity)?.0);
+    }
+
+    pub fn children(&self, entity: Entity) -> &[Entity] {
+        return self.get::<Children>(entity).map_or(&[], |x| &x.0);
+    }
+
+    /// Despawns `entity` along with all of its descendants.
+    pub fn despawn_recursive(&mut self, entity: Entity) -> () {
+        let entities = self.depth_first(entity).collect::<Vec<_>>();
+        self.despawn_batch(&entities);
+    }
+
+    //the parent of `entity`, its parent and so on up to the root
+    pub fn ancestors(&self, entity: Entity) -> Ancestors<'_, A> {
+        return Ancestors {
+            world: self,
+            current: entity,
+        };
+    }
+
+    //every entity below `entity` level by level, not including `entity` itself
+    pub fn descendants(&self, entity: Entity) -> Descendants<'_, A> {
+        return Descendants {
+            world: self,
+            queue: self.children(entity).iter().copied().collect(),
+        };
+    }
+
+    //`entity` and everything below it, each entity followed by its subtree
+    pub fn depth_first(&self, entity: Entity) -> DepthFirst<'_, A> {
+        return DepthFirst {
+            world: self,
+            stack: Vec::from([entity]),
+        };
+    }
+
+    //the world transform of a moved subtree has to be recomputed
+    fn hierarchy_changed(&mut self, _child: Entity) -> () {
+        #[cfg(feature = "transform")]
+        if let Some(local) = self.get_mut::<crate::transform::LocalTransform>(_child) {
+            local.mark_changed();
+        }
+    }
+
+    fn forget_child(&mut self, parent: Entity, child: Entity) -> () {
+        let Some(children) = self.get_mut::<Children>(parent) else {
+            return;
+        };
+        let len = children.0.len();
+        children.0.retain(|x| *x != child);
+        // a list that was emptied by `unlink` is on its way out already
+        if children.0.is_empty() && len != 0 {
+            self.remove::<Children>(parent);
+        }
+    }
+
+    // keeps the other side in sync when `Parent` or `Children` are among the
+    // components `removed` from `entity`, before they are gone
+    pub(crate) fn unlink(&mut self, entity: Entity, removed: &[ComponentId]) -> () {
+        let Some(parent_id) = self.component_id::<Parent>() else {
+            return;
+        };
+        if removed.contains(&parent_id) {
+            if let Some(parent) = self.parent(entity) {
+                self.forget_child(parent, entity);
+                self.hierarchy_changed(entity);
+            }
+        }
+        let children_id = self.component_id::<Children>();
+        if !children_id.is_some_and(|x| removed.contains(&x)) {
+            return;
+        }
+        let Some(children) = self.get_mut::<Children>(entity) else {
+            return;
+        };
+        for child in mem::take(&mut children.0) {
+            unsafe { self.relocate(child, &[], &[parent_id]) };
+        }
+    }
+
+    // cuts the links between the entities in `batch` and the ones that stay,
+    // has to run while the batch still has its components
+    pub(crate) fn detach_batch(&mut self, batch: &FxHashSet<Entity>) -> () {
+        if self.component_id::<Parent>().is_none() {
+            return;
+        }
+        for entity in batch.iter().copied() {
+            if let Some(parent) = self.parent(entity) {
+                if !batch.contains(&parent) {
+                    self.forget_child(parent, entity);
+                }
+            }
+            let children = self.children(entity).to_vec();
+            for child in children.into_iter().filter(|x| !batch.contains(x)) {
+                self.remove_parent(child);
+            }
+        }
+    }
+}
+
+pub struct Ancestors<'w, A>
+where
+    A: WorldAllocator,
+{
+    world: &'w World<A>,
+    current: Entity,
+}
+
+impl<'w, A> Iterator for Ancestors<'w, A>
+where
+    A: WorldAllocator,
+{
+    type Item = Entity;
+    fn next(&mut self) -> Option<Entity> {
+        self.current = self.world.parent(self.current)?;
+        return Some(self.current);
+    }
+}
+
+pub struct Descendants<'w, A>
+where
+    A: WorldAllocator,
+{
+    world: &'w World<A>,
+    queue: VecDeque<Entity>,
+}
+
+impl<'w, A> Iterator for Descendants<'w, A>
+where
+    A: WorldAllocator,
+{
+    type Item = Entity;
+    fn next(&mut self) -> Option<Entity> {
+        let entity = self.queue.pop_front()?;
+        self.queue
+            .extend(self.world.children(entity).iter().copied());
+        return Some(entity);
+    }
+}
+
+pub struct DepthFirst<'w, A>
+where
+    A: WorldAllocator,
+{
+    world: &'w World<A>,
+    stack: Vec<Entity>,
+}
+
+impl<'w, A> Iterator for DepthFirst<'w, A>
+where
+    A: WorldAllocator,
+{
+    type Item = Entity;
+    fn next(&mut self) -> Option<Entity> {
+        let entity = self.stack.pop()?;
+        // reversed so the first child comes out next
+        self.stack
+            .extend(self.world.children(entity).iter().rev().copied());
+        return Some(entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Children, Parent};
+    use std::{alloc::Global, vec::Vec};
+
+    type World = crate::world::World<Global>;
+
+    #[test]
+    fn hierarchy_set_parent() {
+        let mut w = World::new();
+        let [a, b, c] = [w.spawn(), w.spawn_with((1u32,)), w.spawn()];
+        w.set_parent(b, a);
+        w.add_child(a, c);
+        assert_eq!(w.children(a), [b, c]);
+        assert_eq!(w.parent(b), Some(a));
+        assert_eq!(*w.get::<u32>(b).unwrap(), 1);
+
+        // moving a child updates both parents
+        w.set_parent(b, c);
+        assert_eq!(w.children(a), [c]);
+        assert_eq!(w.children(c), [b]);
+        assert_eq!(w.remove_parent(b), Some(c));
+        assert!(!w.has::<Children>(c));
+        assert!(!w.has::<Parent>(b));
+        assert_eq!(w.remove_parent(b), None);
+    }
+
+    #[test]
+    fn hierarchy_remove_components() {
+        let mut w = World::new();
+        let [a, b, c] = [w.spawn(), w.spawn(), w.spawn()];
+        w.set_parent(b, a);
+        w.set_parent(c, a);
+
+        // removing one side through the generic api detaches the other
+        w.remove::<(Parent,)>(b);
+        assert_eq!(w.children(a), [c]);
+        w.entity_mut(c).remove::<(Parent,)>();
+        assert!(!w.has::<Children>(a));
+
+        w.set_parent(b, a);
+        w.set_parent(c, a);
+        w.remove::<(Children,)>(a);
+        assert_eq!(w.parent(b), None);
+        assert_eq!(w.parent(c), None);
+        w.set_parent(b, c);
+        assert_eq!(w.children(c), [b]);
+    }
+
+    #[test]
+    #[should_panic(expected = "would create a cycle")]
+    fn hierarchy_cycle() {
+        let mut w = World::new();
+        let [a, b, c] = [w.spawn(), w.spawn(), w.spawn()];
+        w.set_parent(b, a);
+        w.set_parent(c, b);
+        w.set_parent(a, c);
+    }
+
+    #[test]
+    fn hierarchy_traversal() {
+        let mut w = World::new();
+        let e = (0..6).map(|_| w.spawn()).collect::<Vec<_>>();
+        //     0
+        //   1   2
+        //  3 4   5
+        w.set_parent(e[1], e[0]);
+        w.set_parent(e[2], e[0]);
+        w.set_parent(e[3], e[1]);
+        w.set_parent(e[4], e[1]);
+        w.set_parent(e[5], e[2]);
+
+        assert_eq!(w.ancestors(e[4]).collect::<Vec<_>>(), [e[1], e[0]]);
+        assert_eq!(w.ancestors(e[0]).count(), 0);
+        assert_eq!(
+            w.descendants(e[0]).collect::<Vec<_>>(),
+            [e[1], e[2], e[3], e[4], e[5]]
+        );
+        assert_eq!(
+            w.depth_first(e[0]).collect::<Vec<_>>(),
+            [e[0], e[1], e[3], e[4], e[2], e[5]]
+        );
+    }
+
+    #[test]
+    fn hierarchy_despawn() {
+        let mut w = World::new();
+        let e = (0..5).map(|_| w.spawn_with((0u8,))).collect::<Vec<_>>();
+        w.set_parent(e[1], e[0]);
+        w.set_parent(e[2], e[1]);
+        w.set_parent(e[3], e[1]);
+        w.set_parent(e[4], e[0]);
+
+        w.despawn_recursive(e[1]);
+        assert_eq!(w.children(e[0]), [e[4]]);
+        assert_eq!(w.query::<u8>().count(), 2);
+
+        // children of a despawned entity become roots
+        w.destroy(e[0]);
+        assert_eq!(w.parent(e[4]), None);
+    }
+}
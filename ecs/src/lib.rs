@@ -1,4 +1,7 @@
 #![feature(allocator_api)]
+// lets plain `'static` types and `Component`s share a bundle
+#![feature(specialization)]
+#![allow(incomplete_features)]
 #![no_std]
 
 #[cfg(any(test, feature = "std"))]
@@ -8,6 +11,20 @@ extern crate alloc;
 
 pub mod archetype;
 pub mod bundle;
+pub mod command;
+pub mod component;
 pub mod entity;
+pub mod hierarchy;
+pub mod observer;
+pub mod prefab;
 pub mod query;
+pub mod registry;
+pub mod relation;
+#[cfg(feature = "serde")]
+pub mod serialization;
+pub mod snapshot;
+pub mod sparse;
+pub mod transfer;
+#[cfg(feature = "transform")]
+pub mod transform;
 pub mod world;
@@ -0,0 +1,344 @@
+use alloc::{boxed::Box, vec::Vec};
+use core::{
+    alloc::Allocator,
+    any::TypeId,
+    marker::PhantomData,
+    mem,
+    ptr::{self, NonNull},
+};
+
+use rustc_hash::FxHashMap;
+
+use crate::{
+    component::{Component, ComponentId, ComponentInfo},
+    entity::Entity,
+    world::{DeferredWorld, World, WorldAllocator},
+};
+
+/// What observers are registered for.
+#[derive(Eq, Hash, PartialEq, Clone, Copy, Debug)]
+pub enum EventKey {
+    Event(TypeId),
+    Add(ComponentId),
+    Insert(ComponentId),
+    Remove(ComponentId),
+}
+
+/// Anything that can be passed to `World::trigger`.
+pub trait Event: 'static {}
+
+/// Events observers can be registered for, the `Event`s along with `OnAdd`,
+/// `OnInsert` and `OnRemove`.
+///
+/// Sealed so the key an event is looked up with always matches its type.
+pub trait Observable: sealed::Keyed {}
+
+impl<E> Observable for E where E: sealed::Keyed {}
+
+mod sealed {
+    use super::{Event, EventKey};
+    use crate::world::{World, WorldAllocator};
+    use core::any::TypeId;
+
+    pub trait Keyed: 'static {
+        fn key<A>(world: &mut World<A>) -> EventKey
+        where
+            A: WorldAllocator;
+    }
+
+    impl<E> Keyed for E
+    where
+        E: Event,
+    {
+        fn key<A>(_world: &mut World<A>) -> EventKey
+        where
+            A: WorldAllocator,
+        {
+            return EventKey::Event(TypeId::of::<E>());
+        }
+    }
+}
+
+/// Triggered after `T` was added to an entity that didn't have it.
+pub struct OnAdd<T>(PhantomData<T>);
+
+/// Triggered after `T` was added or replaced.
+pub struct OnInsert<T>(PhantomData<T>);
+
+/// Triggered before `T` is removed, also when the entity gets despawned.
+pub struct OnRemove<T>(PhantomData<T>);
+
+macro_rules! impl_lifecycle_event {
+    ($($E:ident => $key:ident),*) => {
+        $(impl<T> sealed::Keyed for $E<T>
+        where
+            T: Component,
+        {
+            fn key<A>(world: &mut World<A>) -> EventKey
+            where
+                A: WorldAllocator,
+            {
+                return EventKey::$key(world.init_component(ComponentInfo::new::<T>()));
+            }
+        })*
+    };
+}
+
+impl_lifecycle_event!(OnAdd => Add, OnInsert => Insert, OnRemove => Remove);
+
+/// The event an observer runs for and the entity it targets, if any.
+pub struct Trigger<'a, E> {
+    event: &'a E,
+    entity: Option<Entity>,
+}
+
+impl<'a, E> Trigger<'a, E> {
+    pub fn event(&self) -> &'a E {
+        return self.event;
+    }
+
+    pub fn entity(&self) -> Option<Entity> {
+        return self.entity;
+    }
+}
+
+#[derive(Eq, Hash, PartialEq, Clone, Copy, Debug)]
+pub struct ObserverId(u64);
+
+//the event is type erased, null for lifecycle events which carry no data
+type ObserverFn<A> = Box<dyn FnMut(*const u8, Option<Entity>, &mut DeferredWorld<A>) -> ()>;
+
+//an event along with its type, `None` for lifecycle events
+type ErasedEvent = Option<(TypeId, *const u8)>;
+
+struct Observer<A>
+where
+    A: Allocator,
+{
+    id: ObserverId,
+    //the type of the events it casts to, `None` for lifecycle events
+    event: Option<TypeId>,
+    //`None` observes every entity
+    entities: Option<Box<[Entity]>>,
+    run: ObserverFn<A>,
+}
+
+pub(crate) struct Observers<A>
+where
+    A: Allocator,
+{
+    next_id: u64,
+    by_key: FxHashMap<EventKey, Vec<Observer<A>>>,
+}
+
+impl<A> Observers<A>
+where
+    A: Allocator,
+{
+    pub fn new() -> Self {
+        Self {
+            next_id: 0,
+            by_key: FxHashMap::default(),
+        }
+    }
+}
+
+impl<A> World<A>
+where
+    A: WorldAllocator,
+{
+    /// Runs `callback` every time `E` is triggered.
+    pub fn observe<E, F>(&mut self, callback: F) -> ObserverId
+    where
+        E: Observable,
+        F: FnMut(Trigger<'_, E>, &mut DeferredWorld<'_, A>) -> () + 'static,
+    {
+        return self.add_observer(None, callback);
+    }
+
+    /// Runs `callback` every time `E` is triggered for one of `entities`.
+    pub fn observe_entities<E, F>(&mut self, entities: &[Entity], callback: F) -> ObserverId
+    where
+        E: Observable,
+        F: FnMut(Trigger<'_, E>, &mut DeferredWorld<'_, A>) -> () + 'static,
+    {
+        return self.add_observer(Some(entities.into()), callback);
+    }
+
+    fn add_observer<E, F>(&mut self, entities: Option<Box<[Entity]>>, mut callback: F) -> ObserverId
+    where
+        E: Observable,
+        F: FnMut(Trigger<'_, E>, &mut DeferredWorld<'_, A>) -> () + 'static,
+    {
+        let key = E::key(self);
+        let event = match key {
+            EventKey::Event(_) => Some(TypeId::of::<E>()),
+            _ => None,
+        };
+        // lifecycle events are only looked for in worlds that have hooks
+        self.hooked |= event.is_none();
+
+        let id = ObserverId(self.observers.next_id);
+        self.observers.next_id += 1;
+        let run = move |event: *const u8, entity, world: &mut DeferredWorld<A>| {
+            let event = match event.is_null() {
+                true => {
+                    // lifecycle events are zero sized markers
+                    assert!(mem::size_of::<E>() == 0);
+                    NonNull::<E>::dangling().as_ptr()
+                }
+                false => event as *const E,
+            };
+            let event = unsafe { &*event };
+            callback(Trigger { event, entity }, world);
+        };
+        self.observers
+            .by_key
+            .entry(key)
+            .or_default()
+            .push(Observer {
+                id,
+                event,
+                entities,
+                run: Box::new(run),
+            });
+        return id;
+    }
+
+    pub fn remove_observer(&mut self, id: ObserverId) -> bool {
+        for observers in self.observers.by_key.values_mut() {
+            if let Some(index) = observers.iter().position(|x| x.id == id) {
+                observers.remove(index);
+                return true;
+            }
+        }
+        return false;
+    }
+
+    /// Runs the observers of `E` right away, once for each of `entities` or
+    /// once without an entity if there are none.
+    pub fn trigger<E>(&mut self, event: E, entities: &[Entity]) -> ()
+    where
+        E: Event,
+    {
+        let key = EventKey::Event(TypeId::of::<E>());
+        let erased = Some((TypeId::of::<E>(), &event as *const E as *const u8));
+        self.run_observers(key, erased, entities);
+        self.flush();
+    }
+}
+
+impl<A> World<A>
+where
+    A: Allocator,
+{
+    pub(crate) fn run_observers(&mut self, key: EventKey, event: ErasedEvent, entities: &[Entity]) {
+        // observers can't be changed while they run, hooks and observers only
+        // get a `DeferredWorld`
+        let Some(mut observers) = self.observers.by_key.remove(&key) else {
+            return;
+        };
+        // observers cast the event back to the type they were registered with
+        for observer in observers.iter() {
+            assert_eq!(observer.event, event.map(|x| x.0), "event type mismatch");
+        }
+        let event = event.map_or(ptr::null(), |x| x.1);
+        if entities.is_empty() {
+            for observer in observers.iter_mut().filter(|x| x.entities.is_none()) {
+                (observer.run)(event, None, &mut DeferredWorld::new(self));
+            }
+        }
+        for entity in entities {
+            let observing = observers.iter_mut().filter(|x| {
+                return x.entities.as_ref().is_none_or(|x| x.contains(entity));
+            });
+            for observer in observing {
+                (observer.run)(event, Some(*entity), &mut DeferredWorld::new(self));
+            }
+        }
+        self.observers.by_key.insert(key, observers);
+    }
+
+    pub(crate) fn run_lifecycle_observers(&mut self, key: EventKey, entity: Entity) -> () {
+        self.run_observers(key, None, &[entity]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Event, OnAdd, OnRemove};
+    use crate::{component::Component, entity::Entity};
+    use std::{alloc::Global, cell::RefCell, rc::Rc, vec::Vec};
+
+    type World = crate::world::World<Global>;
+
+    struct Damaged {
+        amount: u32,
+    }
+    impl Event for Damaged {}
+
+    struct Health(u32);
+    impl Component for Health {}
+
+    #[test]
+    fn observer_trigger() {
+        let mut w = World::new();
+        let a = w.spawn_with((Health(10),));
+        let b = w.spawn_with((Health(10),));
+        let seen = Rc::new(RefCell::new(Vec::new()));
+
+        let log = seen.clone();
+        w.observe::<Damaged, _>(move |trigger, world| {
+            let entity = trigger.entity();
+            log.borrow_mut().push(entity);
+            if let Some(health) = entity.and_then(|x| world.get_mut::<Health>(x)) {
+                health.0 -= trigger.event().amount;
+            }
+        });
+        let log = seen.clone();
+        let scoped = w.observe_entities::<Damaged, _>(&[b], move |trigger, _| {
+            log.borrow_mut()
+                .push(trigger.entity().map(|x| Entity(x.0 + 100)));
+        });
+
+        w.trigger(Damaged { amount: 3 }, &[a, b]);
+        w.trigger(Damaged { amount: 1 }, &[]);
+        assert_eq!(w.get::<Health>(a).unwrap().0, 7);
+        assert_eq!(w.get::<Health>(b).unwrap().0, 7);
+        assert_eq!(
+            *seen.borrow(),
+            [Some(a), Some(b), Some(Entity(b.0 + 100)), None]
+        );
+
+        assert!(w.remove_observer(scoped));
+        assert!(!w.remove_observer(scoped));
+        seen.borrow_mut().clear();
+        w.trigger(Damaged { amount: 1 }, &[b]);
+        assert_eq!(*seen.borrow(), [Some(b)]);
+    }
+
+    #[test]
+    fn observer_lifecycle() {
+        let mut w = World::new();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let log = seen.clone();
+        w.observe::<OnAdd<Health>, _>(move |trigger, world| {
+            let entity = trigger.entity().unwrap();
+            log.borrow_mut().push(("add", entity));
+            // structural changes go through the command buffer
+            world.commands().add(entity, (0u8,));
+        });
+        let log = seen.clone();
+        w.observe::<OnRemove<Health>, _>(move |trigger, world| {
+            let entity = trigger.entity().unwrap();
+            assert!(world.has::<Health>(entity));
+            log.borrow_mut().push(("remove", entity));
+        });
+
+        let a = w.spawn_with((Health(1),));
+        assert!(w.has::<u8>(a));
+        w.add(a, (Health(2),));
+        w.destroy(a);
+        assert_eq!(*seen.borrow(), [("add", a), ("remove", a)]);
+    }
+}
@@ -0,0 +1,359 @@
+use alloc::vec::Vec;
+use core::{alloc::Allocator, mem, ptr::NonNull};
+
+use crate::{
+    archetype::TypeInfo,
+    bundle::Bundle,
+    component::ComponentId,
+    entity::Entity,
+    hierarchy::{Children, Parent},
+    registry::{CloneFn, TypeRegistration},
+    world::{World, WorldAllocator, WorldId},
+};
+
+/// A stored set of components that can be spawned any number of times.
+///
+/// It refers to components by id, so it can only be instantiated in the world
+/// that made it.
+pub struct Prefab<A>
+where
+    A: Allocator,
+{
+    world: WorldId,
+    components: Vec<(ComponentId, TypeInfo, CloneFn, *mut u8)>,
+    allocator: A,
+}
+
+impl<A> Prefab<A>
+where
+    A: Allocator,
+{
+    pub fn len(&self) -> usize {
+        return self.components.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.components.is_empty();
+    }
+
+    pub fn component_ids(&self) -> impl Iterator<Item = ComponentId> + '_ {
+        return self.components.iter().map(|(x, ..)| *x);
+    }
+}
+
+impl<A> Drop for Prefab<A>
+where
+    A: Allocator,
+{
+    fn drop(&mut self) {
+        for (_, ty, _, data) in self.components.drain(..) {
+            unsafe {
+                ty.drop(data);
+                self.allocator
+                    .deallocate(NonNull::new(data).unwrap(), ty.layout());
+            }
+        }
+    }
+}
+
+impl<A> World<A>
+where
+    A: WorldAllocator,
+{
+    //the registration of the type a component stores
+    pub(crate) fn registration_of(&self, id: ComponentId) -> Option<&TypeRegistration> {
+        let info = self.component_info_by_id(id)?.type_info;
+        let type_id = info
+            .type_id()
+            .or_else(|| self.relations.pair_of(id).map(|x| x.0));
+        return self.type_registration(type_id?);
+    }
+
+    //the clone function registered for a component, pairs use the one of their relation
+    pub(crate) fn clone_fn(&self, id: ComponentId) -> CloneFn {
+        let info = self.component_info_by_id(id).unwrap().type_info;
+        let clone = self.registration_of(id).and_then(|x| x.clone_fn());
+        return clone.unwrap_or_else(|| {
+            panic!(
+                "the component `{}` can't be cloned, register it with `with_clone`",
+                info.name()
+            )
+        });
+    }
+
+    //copies of every component of `entity` except the hierarchy, in buffers
+    //allocated with the world allocator
+    fn clone_components(&self, entity: Entity) -> Vec<(ComponentId, TypeInfo, CloneFn, *mut u8)> {
+        let hierarchy = [
+            self.component_id::<Parent>(),
+            self.component_id::<Children>(),
+        ];
+        // every clone fn is looked up first so a missing one doesn't leak copies
+        let clones = self
+            .ids_of(entity)
+            .into_iter()
+            .filter(|x| !hierarchy.contains(&Some(*x)))
+            .map(|x| (x, self.clone_fn(x)))
+            .collect::<Vec<_>>();
+        return clones
+            .into_iter()
+            .map(|(id, clone)| {
+                let ty = self.component_info_by_id(id).unwrap().type_info;
+                let src = self.get_by_id(entity, id).unwrap();
+                let dst = self.allocator.allocate(ty.layout()).unwrap().as_ptr() as *mut u8;
+                unsafe { clone(src, dst) };
+                return (id, ty, clone, dst);
+            })
+            .collect();
+    }
+
+    //spawns an entity owning the values behind `components`
+    fn spawn_buffers(
+        &mut self,
+        components: &[(ComponentId, TypeInfo, CloneFn, *mut u8)],
+    ) -> Entity {
+        let entity = self.spawn();
+        let added = components
+            .iter()
+            .map(|(id, _, _, data)| (*id, *data))
+            .collect::<Vec<_>>();
+        unsafe { self.relocate(entity, &added, &[]) };
+        for (_, ty, _, data) in components {
+            unsafe {
+                self.allocator
+                    .deallocate(NonNull::new(*data).unwrap(), ty.layout())
+            };
+        }
+        self.flush();
+        return entity;
+    }
+
+    /// Spawns a copy of `entity` that ends up in the same archetype, none if
+    /// `entity` doesn't exist.
+    ///
+    /// Every component has to be registered with `with_clone`. The copy gets
+    /// the same parent but no children.
+    pub fn clone_entity(&mut self, entity: Entity) -> Option<Entity> {
+        if !self.entities.contains(&entity) {
+            return None;
+        }
+        let components = self.clone_components(entity);
+        let clone = self.spawn_buffers(&components);
+        if let Some(parent) = self.parent(entity) {
+            self.set_parent(clone, parent);
+        }
+        return Some(clone);
+    }
+
+    //stores `bundle` so copies of it can be spawned later
+    pub fn prefab<'b, T>(&mut self, mut bundle: T) -> Prefab<A>
+    where
+        T: Bundle<'b>,
+    {
+        // like `clone_components`, a missing clone fn panics before anything is copied
+        let clones = self
+            .init_bundle::<T>()
+            .into_iter()
+            .map(|x| (x, self.clone_fn(x)))
+            .collect::<Vec<_>>();
+        let ptrs = unsafe { bundle.as_ptrs() };
+        let components = clones
+            .into_iter()
+            .zip(ptrs.iter())
+            .map(|((id, clone), src)| {
+                let ty = self.component_info_by_id(id).unwrap().type_info;
+                let dst = self.allocator.allocate(ty.layout()).unwrap().as_ptr() as *mut u8;
+                unsafe { core::ptr::copy_nonoverlapping(*src, dst, ty.layout().size()) };
+                return (id, ty, clone, dst);
+            })
+            .collect();
+        // the values were moved into the prefab
+        mem::forget(bundle);
+        return Prefab {
+            world: self.id,
+            components,
+            allocator: self.allocator.clone(),
+        };
+    }
+
+    //a prefab made of copies of the components `entity` has right now
+    pub fn prefab_of(&self, entity: Entity) -> Option<Prefab<A>> {
+        if !self.entities.contains(&entity) {
+            return None;
+        }
+        return Some(Prefab {
+            world: self.id,
+            components: self.clone_components(entity),
+            allocator: self.allocator.clone(),
+        });
+    }
+
+    /// Spawns a copy of the components of `prefab`.
+    ///
+    /// Panics if `prefab` was made by another world or refers to pairs
+    /// forgotten by `restore`.
+    pub fn instantiate(&mut self, prefab: &Prefab<A>) -> Entity {
+        assert_eq!(
+            prefab.world, self.id,
+            "the prefab was made by another world"
+        );
+        // a restored snapshot hands the ids of later pairs out again
+        let known = prefab.components.iter().all(|(id, ty, ..)| {
+            !self.relations.is_free(*id)
+                && self.component_info_by_id(*id).is_some_and(|x| {
+                    x.type_info.name() == ty.name() && x.type_info.layout() == ty.layout()
+                })
+        });
+        assert!(known, "the prefab refers to components the world forgot");
+        let components = prefab
+            .components
+            .iter()
+            .map(|(id, ty, clone, src)| {
+                let dst = self.allocator.allocate(ty.layout()).unwrap().as_ptr() as *mut u8;
+                unsafe { clone(*src, dst) };
+                return (*id, *ty, *clone, dst);
+            })
+            .collect::<Vec<_>>();
+        return self.spawn_buffers(&components);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::component::{Component, StorageType};
+    use core::{
+        alloc::{AllocError, Allocator, Layout},
+        cell::Cell,
+        ptr::NonNull,
+    };
+    use std::{alloc::Global, panic::AssertUnwindSafe, rc::Rc, string::String};
+
+    type World = crate::world::World<Global>;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Selected;
+    impl Component for Selected {
+        const STORAGE: StorageType = StorageType::Sparse;
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Likes;
+    impl Component for Likes {}
+
+    fn world() -> World {
+        let mut w = World::new();
+        w.register_type::<u32>().with_clone();
+        w.register_type::<String>().with_clone();
+        w.register_type::<Selected>().with_clone();
+        w.register_type::<Likes>().with_clone();
+        w.register_type::<Rc<()>>().with_clone();
+        return w;
+    }
+
+    #[test]
+    fn clone_entity() {
+        let mut w = world();
+        let parent = w.spawn();
+        let friend = w.spawn();
+        let a = w.spawn_with((3u32, String::from("a"), Selected));
+        w.add_pair(a, Likes, friend);
+        w.set_parent(a, parent);
+
+        let b = w.clone_entity(a).unwrap();
+        assert!(core::ptr::eq(
+            w.archetypes.get_by_entity(a).unwrap(),
+            w.archetypes.get_by_entity(b).unwrap()
+        ));
+        w.get_mut::<String>(b).unwrap().push('b');
+        assert_eq!(w.get::<String>(a).unwrap(), "a");
+        assert_eq!(w.get::<String>(b).unwrap(), "ab");
+        assert_eq!(*w.get::<u32>(b).unwrap(), 3);
+        assert!(w.has::<Selected>(b));
+        assert!(w.has_pair::<Likes>(b, friend));
+        assert_eq!(w.children(parent), [a, b]);
+
+        // children stay with the original
+        let copy = w.clone_entity(parent).unwrap();
+        assert!(w.children(copy).is_empty());
+        assert_eq!(w.children(parent), [a, b]);
+    }
+
+    #[test]
+    #[should_panic(expected = "register it with `with_clone`")]
+    fn clone_unregistered() {
+        let mut w = world();
+        let a = w.spawn_with((3u32, 1u8));
+        w.clone_entity(a);
+    }
+
+    #[test]
+    fn clone_dead_entity() {
+        let mut w = world();
+        let a = w.spawn_with((3u32,));
+        w.destroy(a);
+        assert!(w.clone_entity(a).is_none());
+        assert!(w.prefab_of(a).is_none());
+        assert_eq!(w.query::<u32>().count(), 0);
+    }
+
+    // counts the allocations that are still alive
+    #[derive(Clone, Default)]
+    struct Live(Rc<Cell<isize>>);
+
+    unsafe impl Allocator for Live {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            self.0.set(self.0.get() + 1);
+            return Global.allocate(layout);
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.0.set(self.0.get() - 1);
+            Global.deallocate(ptr, layout);
+        }
+    }
+
+    #[test]
+    fn prefab_unregistered() {
+        let live = Live::default();
+        let mut w = crate::world::World::new_in(live.clone());
+        w.register_type::<Rc<()>>().with_clone();
+        let rc = Rc::new(());
+        let prefab = || w.prefab((rc.clone(), 1u8));
+        assert!(std::panic::catch_unwind(AssertUnwindSafe(prefab)).is_err());
+        // nothing got copied out of the bundle before the panic
+        assert_eq!(live.0.get(), 0);
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "made by another world")]
+    fn prefab_other_world() {
+        let mut w = world();
+        let prefab = w.prefab((7u32,));
+        let mut other = world();
+        // the ids line up, so without the check this would spawn a `String`
+        other.spawn_with((String::new(),));
+        other.instantiate(&prefab);
+    }
+
+    #[test]
+    fn prefab_instantiate() {
+        let mut w = world();
+        let rc = Rc::new(());
+        let prefab = w.prefab((7u32, rc.clone()));
+        assert_eq!(prefab.len(), 2);
+        let a = w.instantiate(&prefab);
+        let b = w.instantiate(&prefab);
+        assert_ne!(a, b);
+        assert_eq!(*w.get::<u32>(b).unwrap(), 7);
+        assert_eq!(Rc::strong_count(&rc), 4);
+
+        let copy = w.prefab_of(a).unwrap();
+        w.destroy(a);
+        let c = w.instantiate(&copy);
+        assert_eq!(*w.get::<u32>(c).unwrap(), 7);
+        drop(prefab);
+        drop(copy);
+        assert_eq!(Rc::strong_count(&rc), 3);
+    }
+}
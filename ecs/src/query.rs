@@ -1,13 +1,44 @@
-use crate::{archetype::Archetype, bundle::Bundle};
+use crate::{
+    archetype::Archetype, bundle::Bundle, component::ComponentId, entity::Entity, sparse::SparseSet,
+};
 use alloc::boxed::Box;
 use core::{alloc::Allocator, marker::PhantomData};
 
+//the sparse set each queried component lives in, `None` for archetype columns
+pub(crate) type SparseColumns<'a, A> = Box<[Option<&'a SparseSet<A>>]>;
+
+//row `index` of `archetype`, none if the entity lacks one of the sparse components
+unsafe fn fetch<A>(
+    archetype: &Archetype<A>,
+    index: usize,
+    ids: &[ComponentId],
+    sparse: &[Option<&SparseSet<A>>],
+) -> Option<Box<[*mut u8]>>
+where
+    A: Allocator,
+{
+    if sparse.iter().all(Option::is_none) {
+        return archetype.get_by_index(index, ids);
+    }
+    let entity = archetype.entities[index];
+    return ids
+        .iter()
+        .zip(sparse)
+        .map(|(id, set)| match set {
+            Some(set) => set.get(entity),
+            None => Some(archetype.get_by_index(index, &[*id])?[0]),
+        })
+        .collect();
+}
+
 pub struct Query<'a, T, A>
 where
     T: Bundle<'a>,
     A: Allocator,
 {
     archetypes: Box<[&'a Archetype<A>]>,
+    ids: Box<[ComponentId]>,
+    sparse: SparseColumns<'a, A>,
     archetype_index: usize,
     current_index: usize,
     _phantom_data: PhantomData<T>,
@@ -18,9 +49,15 @@ where
     T: Bundle<'a>,
     A: Allocator,
 {
-    pub fn new(archetypes: Box<[&'a Archetype<A>]>) -> Self {
+    pub fn new(
+        archetypes: Box<[&'a Archetype<A>]>,
+        ids: Box<[ComponentId]>,
+        sparse: SparseColumns<'a, A>,
+    ) -> Self {
         Self {
             archetypes,
+            ids,
+            sparse,
             archetype_index: 0,
             current_index: 0,
             _phantom_data: PhantomData::default(),
@@ -28,31 +65,46 @@ where
     }
 }
 
-impl<'a, T, A> Iterator for Query<'a, T, A>
+impl<'a, T, A> Query<'a, T, A>
 where
     T: Bundle<'a> + 'a,
     A: Allocator,
 {
-    type Item = T::Target;
-    fn next(&mut self) -> Option<Self::Item> {
-        let archetype = &self.archetypes[self.archetype_index];
+    /// Hands out the entity of every row along with its components.
+    pub fn with_entities(mut self) -> impl Iterator<Item = (Entity, T::Target)> {
+        return core::iter::from_fn(move || {
+            let (entity, data) = self.next_row()?;
+            return Some((entity, unsafe { T::from_ptr(&data) }));
+        });
+    }
 
-        if self.current_index >= archetype.len() {
-            self.archetype_index += 1;
-            if self.archetype_index >= self.archetypes.len() {
-                return None;
+    fn next_row(&mut self) -> Option<(Entity, Box<[*mut u8]>)> {
+        loop {
+            // skip over the archetypes that are exhausted or empty
+            while self.current_index >= self.archetypes.get(self.archetype_index)?.len() {
+                self.archetype_index += 1;
+                self.current_index = 0;
             }
-            self.current_index = 0;
-        }
 
-        let archetype = &self.archetypes[self.archetype_index];
-        let data = unsafe {
-            archetype
-                .get_by_index(self.current_index, &T::type_ids())
-                .unwrap()
-        };
+            let archetype = &self.archetypes[self.archetype_index];
+            let index = self.current_index;
+            self.current_index += 1;
+            // rows missing a sparse component aren't part of the query
+            if let Some(data) = unsafe { fetch(archetype, index, &self.ids, &self.sparse) } {
+                return Some((archetype.entities[index], data));
+            }
+        }
+    }
+}
 
-        self.current_index += 1;
+impl<'a, T, A> Iterator for Query<'a, T, A>
+where
+    T: Bundle<'a> + 'a,
+    A: Allocator,
+{
+    type Item = T::Target;
+    fn next(&mut self) -> Option<Self::Item> {
+        let (_, data) = self.next_row()?;
         return Some(unsafe { T::from_ptr(&data) });
     }
 }
@@ -64,6 +116,8 @@ where
     A: Allocator,
 {
     archetypes: Box<[&'a mut Archetype<A>]>,
+    ids: Box<[ComponentId]>,
+    sparse: SparseColumns<'a, A>,
     archetype_index: usize,
     current_index: usize,
     _phantom_data: PhantomData<T>,
@@ -74,9 +128,15 @@ where
     T: Bundle<'a>,
     A: Allocator,
 {
-    pub fn new(archetypes: Box<[&'a mut Archetype<A>]>) -> Self {
+    pub fn new(
+        archetypes: Box<[&'a mut Archetype<A>]>,
+        ids: Box<[ComponentId]>,
+        sparse: SparseColumns<'a, A>,
+    ) -> Self {
         Self {
             archetypes,
+            ids,
+            sparse,
             archetype_index: 0,
             current_index: 0,
             _phantom_data: PhantomData::default(),
@@ -84,6 +144,38 @@ where
     }
 }
 
+impl<'a, T, A> QueryMut<'a, T, A>
+where
+    T: Bundle<'a> + 'a,
+    A: Allocator,
+{
+    //same as `Query::with_entities`
+    pub fn with_entities(mut self) -> impl Iterator<Item = (Entity, T::TargetMut)> {
+        return core::iter::from_fn(move || {
+            let (entity, data) = self.next_row()?;
+            return Some((entity, unsafe { T::from_ptr_mut(&data) }));
+        });
+    }
+
+    fn next_row(&mut self) -> Option<(Entity, Box<[*mut u8]>)> {
+        loop {
+            // skip over the archetypes that are exhausted or empty
+            while self.current_index >= self.archetypes.get(self.archetype_index)?.len() {
+                self.archetype_index += 1;
+                self.current_index = 0;
+            }
+
+            let archetype = &self.archetypes[self.archetype_index];
+            let index = self.current_index;
+            self.current_index += 1;
+            // rows missing a sparse component aren't part of the query
+            if let Some(data) = unsafe { fetch(archetype, index, &self.ids, &self.sparse) } {
+                return Some((archetype.entities[index], data));
+            }
+        }
+    }
+}
+
 impl<'a, T, A> Iterator for QueryMut<'a, T, A>
 where
     T: Bundle<'a> + 'a,
@@ -91,25 +183,63 @@ where
 {
     type Item = T::TargetMut;
     fn next(&mut self) -> Option<Self::Item> {
-        let archetype = &self.archetypes[self.archetype_index];
+        let (_, data) = self.next_row()?;
+        return Some(unsafe { T::from_ptr_mut(&data) });
+    }
+}
 
-        if self.current_index >= archetype.len() {
-            self.archetype_index += 1;
-            if self.archetype_index >= self.archetypes.len() {
-                return None;
-            }
-            self.current_index = 0;
+/// Query over components picked at runtime, yields raw pointers in the order
+/// the ids were given in.
+pub struct DynamicQuery<'a, A>
+where
+    A: Allocator,
+{
+    archetypes: Box<[&'a Archetype<A>]>,
+    ids: Box<[ComponentId]>,
+    sparse: SparseColumns<'a, A>,
+    archetype_index: usize,
+    current_index: usize,
+}
+
+impl<'a, A> DynamicQuery<'a, A>
+where
+    A: Allocator,
+{
+    pub fn new(
+        archetypes: Box<[&'a Archetype<A>]>,
+        ids: &[ComponentId],
+        sparse: SparseColumns<'a, A>,
+    ) -> Self {
+        Self {
+            archetypes,
+            ids: ids.into(),
+            sparse,
+            archetype_index: 0,
+            current_index: 0,
         }
+    }
+}
 
-        let archetype = &self.archetypes[self.archetype_index];
-        let data = unsafe {
-            archetype
-                .get_by_index(self.current_index, &T::type_ids())
-                .unwrap()
-        };
+impl<'a, A> Iterator for DynamicQuery<'a, A>
+where
+    A: Allocator,
+{
+    type Item = (Entity, Box<[*mut u8]>);
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // skip over the archetypes that are exhausted or empty
+            while self.current_index >= self.archetypes.get(self.archetype_index)?.len() {
+                self.archetype_index += 1;
+                self.current_index = 0;
+            }
 
-        self.current_index += 1;
-        return Some(unsafe { T::from_ptr_mut(&data) });
+            let archetype = &self.archetypes[self.archetype_index];
+            let index = self.current_index;
+            self.current_index += 1;
+            if let Some(data) = unsafe { fetch(archetype, index, &self.ids, &self.sparse) } {
+                return Some((archetype.entities[index], data));
+            }
+        }
     }
 }
 
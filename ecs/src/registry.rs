@@ -0,0 +1,377 @@
+use core::{any::TypeId, fmt, marker::PhantomData};
+
+use rustc_hash::FxHashMap;
+
+use crate::{
+    archetype::TypeInfo,
+    component::Component,
+    entity::{Entity, MapEntities},
+};
+
+pub type CloneFn = unsafe fn(*const u8, *mut u8) -> ();
+pub type DebugFn = unsafe fn(*const u8, &mut fmt::Formatter) -> fmt::Result;
+pub type DefaultFn = unsafe fn(*mut u8) -> ();
+pub type MapEntitiesFn = unsafe fn(*mut u8, &mut dyn FnMut(Entity) -> Entity) -> ();
+#[cfg(feature = "serde")]
+pub type SerializeFn = unsafe fn(*const u8) -> &'static dyn erased_serde::Serialize;
+#[cfg(feature = "serde")]
+pub type DeserializeFn =
+    unsafe fn(&mut dyn erased_serde::Deserializer, *mut u8) -> Result<(), erased_serde::Error>;
+
+/// Metadata of a type under a name that stays the same across builds.
+///
+/// All function pointers work on type erased values of the registered type,
+/// the ones that weren't provided are `None`.
+#[derive(Clone, Copy)]
+pub struct TypeRegistration {
+    name: &'static str,
+    type_info: TypeInfo,
+    clone: Option<CloneFn>,
+    copy: bool,
+    //used as the relation of pairs, only those are saved with their target
+    relation: bool,
+    debug: Option<DebugFn>,
+    default: Option<DefaultFn>,
+    map_entities: Option<MapEntitiesFn>,
+    #[cfg(feature = "serde")]
+    serialize: Option<SerializeFn>,
+    #[cfg(feature = "serde")]
+    deserialize: Option<DeserializeFn>,
+}
+
+impl TypeRegistration {
+    pub fn of<T>(name: &'static str) -> Self
+    where
+        T: Component,
+    {
+        Self {
+            name,
+            type_info: TypeInfo::of::<T>(),
+            clone: None,
+            copy: false,
+            relation: false,
+            debug: None,
+            default: None,
+            map_entities: None,
+            #[cfg(feature = "serde")]
+            serialize: None,
+            #[cfg(feature = "serde")]
+            deserialize: None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        return self.name;
+    }
+
+    pub fn type_info(&self) -> TypeInfo {
+        return self.type_info;
+    }
+
+    pub fn type_id(&self) -> TypeId {
+        return self.type_info.type_id().unwrap();
+    }
+
+    pub fn clone_fn(&self) -> Option<CloneFn> {
+        return self.clone;
+    }
+
+    //set for `Copy` types, their values can be copied bytewise
+    pub fn is_copy(&self) -> bool {
+        return self.copy;
+    }
+
+    pub fn is_relation(&self) -> bool {
+        return self.relation;
+    }
+
+    pub fn debug_fn(&self) -> Option<DebugFn> {
+        return self.debug;
+    }
+
+    pub fn default_fn(&self) -> Option<DefaultFn> {
+        return self.default;
+    }
+
+    pub fn map_entities_fn(&self) -> Option<MapEntitiesFn> {
+        return self.map_entities;
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn serialize_fn(&self) -> Option<SerializeFn> {
+        return self.serialize;
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn deserialize_fn(&self) -> Option<DeserializeFn> {
+        return self.deserialize;
+    }
+
+    /// Formats the value behind `ptr` if the type was registered with debug.
+    ///
+    /// # Safety
+    /// `ptr` has to point at a valid value of the registered type.
+    pub unsafe fn debug(&self, ptr: *const u8) -> Option<impl fmt::Debug> {
+        struct Erased(*const u8, DebugFn);
+
+        impl fmt::Debug for Erased {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                return unsafe { (self.1)(self.0, f) };
+            }
+        }
+
+        return Some(Erased(ptr, self.debug?));
+    }
+}
+
+/// Adds optional metadata to a registration, returned by `TypeRegistry::register`.
+pub struct TypeRegistrar<'r, T> {
+    registration: &'r mut TypeRegistration,
+    marker: PhantomData<T>,
+}
+
+impl<'r, T> TypeRegistrar<'r, T>
+where
+    T: Component,
+{
+    pub fn with_clone(self) -> Self
+    where
+        T: Clone,
+    {
+        unsafe fn clone<T: Clone>(src: *const u8, dst: *mut u8) -> () {
+            dst.cast::<T>().write((*src.cast::<T>()).clone());
+        }
+
+        self.registration.clone = Some(clone::<T>);
+        return self;
+    }
+
+    /// Like `with_clone`, also lets snapshots copy whole columns at once.
+    pub fn with_copy(self) -> Self
+    where
+        T: Copy,
+    {
+        self.registration.copy = true;
+        return self.with_clone();
+    }
+
+    /// Marks `T` as the relation of pairs, which lets them be serialized.
+    pub fn as_relation(self) -> Self {
+        self.registration.relation = true;
+        return self;
+    }
+
+    pub fn with_debug(self) -> Self
+    where
+        T: fmt::Debug,
+    {
+        unsafe fn debug<T: fmt::Debug>(ptr: *const u8, f: &mut fmt::Formatter) -> fmt::Result {
+            return fmt::Debug::fmt(&*ptr.cast::<T>(), f);
+        }
+
+        self.registration.debug = Some(debug::<T>);
+        return self;
+    }
+
+    pub fn with_default(self) -> Self
+    where
+        T: Default,
+    {
+        unsafe fn default<T: Default>(dst: *mut u8) -> () {
+            dst.cast::<T>().write(T::default());
+        }
+
+        self.registration.default = Some(default::<T>);
+        return self;
+    }
+
+    pub fn with_map_entities(self) -> Self
+    where
+        T: MapEntities,
+    {
+        unsafe fn map_entities<T: MapEntities>(
+            ptr: *mut u8,
+            map: &mut dyn FnMut(Entity) -> Entity,
+        ) -> () {
+            (*ptr.cast::<T>()).map_entities(map);
+        }
+
+        self.registration.map_entities = Some(map_entities::<T>);
+        return self;
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn with_serde(self) -> Self
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        unsafe fn serialize<T: serde::Serialize + 'static>(
+            ptr: *const u8,
+        ) -> &'static dyn erased_serde::Serialize {
+            return &*ptr.cast::<T>();
+        }
+        unsafe fn deserialize<T: serde::de::DeserializeOwned>(
+            deserializer: &mut dyn erased_serde::Deserializer,
+            ptr: *mut u8,
+        ) -> Result<(), erased_serde::Error> {
+            let value = erased_serde::deserialize::<T>(deserializer)?;
+            ptr.cast::<T>().write(value);
+            return Ok(());
+        }
+
+        self.registration.serialize = Some(serialize::<T>);
+        self.registration.deserialize = Some(deserialize::<T>);
+        return self;
+    }
+}
+
+/// Looks up type metadata by `TypeId` or by the stable name.
+#[derive(Clone, Default)]
+pub struct TypeRegistry {
+    types: FxHashMap<TypeId, TypeRegistration>,
+    names: FxHashMap<&'static str, TypeId>,
+}
+
+impl TypeRegistry {
+    pub fn new() -> Self {
+        Self {
+            types: FxHashMap::default(),
+            names: FxHashMap::default(),
+        }
+    }
+
+    //registers `T` under the name it declares as a component
+    pub fn register<T>(&mut self) -> TypeRegistrar<'_, T>
+    where
+        T: Component,
+    {
+        return self.register_as::<T>(T::name());
+    }
+
+    /// Registers `T` under `name`, replacing an earlier registration of `T`.
+    ///
+    /// Panics if `name` is already taken by another type.
+    pub fn register_as<T>(&mut self, name: &'static str) -> TypeRegistrar<'_, T>
+    where
+        T: Component,
+    {
+        let id = TypeId::of::<T>();
+        if let Some(other) = self.names.get(name) {
+            assert!(
+                *other == id,
+                "the name `{}` is already registered for another type",
+                name
+            );
+        }
+        if let Some(old) = self.types.get(&id) {
+            self.names.remove(old.name);
+        }
+        self.names.insert(name, id);
+        self.types.insert(id, TypeRegistration::of::<T>(name));
+        return TypeRegistrar {
+            registration: self.types.get_mut(&id).unwrap(),
+            marker: PhantomData,
+        };
+    }
+
+    pub fn get(&self, type_id: TypeId) -> Option<&TypeRegistration> {
+        return self.types.get(&type_id);
+    }
+
+    pub fn get_by_name(&self, name: &str) -> Option<&TypeRegistration> {
+        return self.types.get(self.names.get(name)?);
+    }
+
+    pub fn contains(&self, type_id: TypeId) -> bool {
+        return self.types.contains_key(&type_id);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.types.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.types.is_empty();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TypeRegistration> {
+        return self.types.values();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TypeRegistry;
+    use crate::component::Component;
+    use std::{any::TypeId, format, mem::MaybeUninit};
+
+    #[derive(Clone, Debug, Default, PartialEq)]
+    struct Name(&'static str);
+
+    impl Component for Name {
+        fn name() -> &'static str {
+            return "Name";
+        }
+    }
+
+    #[test]
+    fn registry_lookup() {
+        let mut registry = TypeRegistry::new();
+        registry.register::<Name>().with_debug();
+        registry.register_as::<u32>("u32");
+
+        let by_id = registry.get(TypeId::of::<Name>()).unwrap();
+        let by_name = registry.get_by_name("Name").unwrap();
+        assert_eq!(by_id.type_id(), by_name.type_id());
+        assert_eq!(
+            registry.get_by_name("u32").unwrap().type_id(),
+            TypeId::of::<u32>()
+        );
+        assert!(registry.get_by_name("f32").is_none());
+        assert_eq!(registry.len(), 2);
+    }
+
+    #[test]
+    fn registry_rename() {
+        let mut registry = TypeRegistry::new();
+        registry.register_as::<Name>("old");
+        registry.register_as::<Name>("new");
+        assert!(registry.get_by_name("old").is_none());
+        assert_eq!(registry.get(TypeId::of::<Name>()).unwrap().name(), "new");
+    }
+
+    #[test]
+    #[should_panic(expected = "already registered")]
+    fn registry_name_taken() {
+        let mut registry = TypeRegistry::new();
+        registry.register_as::<Name>("name");
+        registry.register_as::<u32>("name");
+    }
+
+    #[test]
+    fn registry_functions() {
+        let mut registry = TypeRegistry::new();
+        registry
+            .register::<Name>()
+            .with_clone()
+            .with_debug()
+            .with_default();
+        let registration = registry.get_by_name("Name").unwrap();
+
+        let value = Name("a");
+        let mut copy = MaybeUninit::<Name>::uninit();
+        let mut default = MaybeUninit::<Name>::uninit();
+        unsafe {
+            let ptr = &value as *const Name as *const u8;
+            registration.clone_fn().unwrap()(ptr, copy.as_mut_ptr().cast());
+            registration.default_fn().unwrap()(default.as_mut_ptr().cast());
+            assert_eq!(
+                format!("{:?}", registration.debug(ptr).unwrap()),
+                "Name(\"a\")"
+            );
+            assert_eq!(copy.assume_init(), value);
+            assert_eq!(default.assume_init(), Name::default());
+        }
+        assert!(registration.map_entities_fn().is_none());
+    }
+}
@@ -0,0 +1,363 @@
+use alloc::vec::Vec;
+use core::{any::TypeId, mem::ManuallyDrop};
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{
+    archetype::TypeInfo,
+    component::{Component, ComponentId, ComponentInfo},
+    entity::Entity,
+    world::{World, WorldAllocator},
+};
+
+/// The component ids handed out to relationship pairs.
+///
+/// Every `(R, target)` pair is a component of its own, so entities with
+/// different targets end up in different archetypes.
+#[derive(Default, Clone)]
+pub(crate) struct Relations {
+    pairs: FxHashMap<(TypeId, Entity), ComponentId>,
+    //the targets of every relation along with the pair ids
+    by_relation: FxHashMap<TypeId, Vec<(Entity, ComponentId)>>,
+    by_target: FxHashMap<Entity, Vec<(TypeId, ComponentId)>>,
+    by_id: FxHashMap<ComponentId, (TypeId, Entity)>,
+    //ids of pairs whose target is gone, handed out again to new pairs
+    free: Vec<ComponentId>,
+}
+
+impl Relations {
+    pub fn is_empty(&self) -> bool {
+        return self.pairs.is_empty();
+    }
+
+    pub fn get(&self, relation: TypeId, target: Entity) -> Option<ComponentId> {
+        return self.pairs.get(&(relation, target)).copied();
+    }
+
+    pub fn insert(&mut self, relation: TypeId, target: Entity, id: ComponentId) -> () {
+        self.pairs.insert((relation, target), id);
+        self.by_id.insert(id, (relation, target));
+        self.by_relation
+            .entry(relation)
+            .or_default()
+            .push((target, id));
+        self.by_target
+            .entry(target)
+            .or_default()
+            .push((relation, id));
+    }
+
+    //the relation type and target of a pair
+    pub fn pair_of(&self, id: ComponentId) -> Option<(TypeId, Entity)> {
+        return self.by_id.get(&id).copied();
+    }
+
+    pub fn targets(&self, relation: TypeId) -> &[(Entity, ComponentId)] {
+        return self.by_relation.get(&relation).map_or(&[], |x| x);
+    }
+
+    //forgets every pair pointing at `target` and returns their ids
+    pub fn remove_target(&mut self, target: Entity) -> Vec<ComponentId> {
+        let Some(pairs) = self.by_target.remove(&target) else {
+            return Vec::new();
+        };
+        for (relation, id) in pairs.iter() {
+            self.pairs.remove(&(*relation, target));
+            self.by_id.remove(id);
+            if let Some(targets) = self.by_relation.get_mut(relation) {
+                targets.retain(|(x, _)| *x != target);
+            }
+        }
+        return pairs.into_iter().map(|(_, x)| x).collect();
+    }
+
+    //the id of every pair
+    pub fn ids(&self) -> impl Iterator<Item = ComponentId> + '_ {
+        return self.by_id.keys().copied();
+    }
+
+    //the ids of every pair along with the free ones
+    pub fn all_ids(&self) -> impl Iterator<Item = ComponentId> + '_ {
+        return self.ids().chain(self.free.iter().copied());
+    }
+
+    pub fn is_free(&self, id: ComponentId) -> bool {
+        return self.free.contains(&id);
+    }
+
+    //hands `ids` out again to new pairs
+    pub fn release(&mut self, mut ids: Vec<ComponentId>) -> () {
+        ids.sort();
+        self.free.extend(ids);
+    }
+}
+
+impl<A> World<A>
+where
+    A: WorldAllocator,
+{
+    //the id of the pair, handing out a new one the first time it shows up
+    fn init_pair<R>(&mut self, target: Entity) -> ComponentId
+    where
+        R: Component,
+    {
+        return self.init_pair_by_id(TypeId::of::<R>(), TypeInfo::pair::<R>(), target);
+    }
+
+    pub(crate) fn init_pair_by_id(
+        &mut self,
+        relation: TypeId,
+        type_info: TypeInfo,
+        target: Entity,
+    ) -> ComponentId {
+        if let Some(id) = self.relations.get(relation, target) {
+            return id;
+        }
+        let info = ComponentInfo::dynamic(type_info);
+        let id = match self.relations.free.pop() {
+            Some(id) => {
+                self.components[id.index()] = info;
+                id
+            }
+            None => self.push_component(info),
+        };
+        self.relations.insert(relation, target, id);
+        return id;
+    }
+
+    pub fn pair_id<R>(&self, target: Entity) -> Option<ComponentId>
+    where
+        R: Component,
+    {
+        return self.relations.get(TypeId::of::<R>(), target);
+    }
+
+    /// Adds the relationship `(R, target)` to `entity`, replacing the value an
+    /// earlier pair with the same target had.
+    pub fn add_pair<R>(&mut self, entity: Entity, relation: R, target: Entity) -> ()
+    where
+        R: Component,
+    {
+        let id = self.init_pair::<R>(target);
+        let relation = ManuallyDrop::new(relation);
+        unsafe { self.insert_by_id(entity, id, &*relation as *const R as *const u8) };
+    }
+
+    pub fn remove_pair<R>(&mut self, entity: Entity, target: Entity) -> Option<()>
+    where
+        R: Component,
+    {
+        return self.remove_by_id(entity, self.pair_id::<R>(target)?);
+    }
+
+    pub fn has_pair<R>(&self, entity: Entity, target: Entity) -> bool
+    where
+        R: Component,
+    {
+        return self.get_pair::<R>(entity, target).is_some();
+    }
+
+    pub fn get_pair<R>(&self, entity: Entity, target: Entity) -> Option<&R>
+    where
+        R: Component,
+    {
+        let data = self.get_by_id(entity, self.pair_id::<R>(target)?)?;
+        return Some(unsafe { &*data.cast::<R>() });
+    }
+
+    pub fn get_pair_mut<R>(&mut self, entity: Entity, target: Entity) -> Option<&mut R>
+    where
+        R: Component,
+    {
+        let data = self.get_mut_by_id(entity, self.pair_id::<R>(target)?)?;
+        return Some(unsafe { &mut *data.cast::<R>() });
+    }
+
+    //every entity `entity` has an `R` relationship with
+    pub fn targets<R>(&self, entity: Entity) -> impl Iterator<Item = Entity> + '_
+    where
+        R: Component,
+    {
+        let archetype = self.archetypes.get_by_entity(entity);
+        return self
+            .relations
+            .targets(TypeId::of::<R>())
+            .iter()
+            .filter(move |(_, id)| archetype.is_some_and(|x| x.contains(id)))
+            .map(|(x, _)| *x);
+    }
+
+    /// Every entity with the relationship `(R, target)` along with its value.
+    pub fn query_pair<R>(&self, target: Entity) -> impl Iterator<Item = (Entity, &R)>
+    where
+        R: Component,
+    {
+        return self
+            .pair_id::<R>(target)
+            .into_iter()
+            .flat_map(|id| self.pair_column::<R>(id));
+    }
+
+    /// Every `R` relationship no matter the target, as `(entity, target, value)`.
+    ///
+    /// Entities that relate to several targets show up once per target.
+    pub fn query_wildcard<R>(&self) -> impl Iterator<Item = (Entity, Entity, &R)>
+    where
+        R: Component,
+    {
+        return self
+            .relations
+            .targets(TypeId::of::<R>())
+            .iter()
+            .flat_map(|(target, id)| {
+                return self
+                    .pair_column::<R>(*id)
+                    .map(|(entity, value)| (entity, *target, value));
+            });
+    }
+
+    fn pair_column<R>(&self, id: ComponentId) -> impl Iterator<Item = (Entity, &R)>
+    where
+        R: Component,
+    {
+        let archetypes = self.archetypes.get_similiar(&[id]).unwrap_or_default();
+        return archetypes
+            .into_vec()
+            .into_iter()
+            .flat_map(move |archetype| {
+                return (0..archetype.len()).map(move |i| {
+                    let data = unsafe { archetype.get_by_index(i, &[id]).unwrap() };
+                    return (archetype.entities[i], unsafe { &*data[0].cast::<R>() });
+                });
+            });
+    }
+
+    // drops the pairs pointing at entities of `batch` from the ones that stay
+    // and frees their ids
+    pub(crate) fn remove_targets(&mut self, batch: &FxHashSet<Entity>) -> () {
+        if self.relations.is_empty() {
+            return;
+        }
+        let mut freed = Vec::new();
+        for target in batch.iter() {
+            for id in self.relations.remove_target(*target) {
+                freed.push(id);
+                let sources = self
+                    .archetypes
+                    .get_similiar(&[id])
+                    .unwrap_or_default()
+                    .iter()
+                    .flat_map(|x| x.entity_iter())
+                    .filter(|x| !batch.contains(x))
+                    .copied()
+                    .collect::<Vec<_>>();
+                for source in sources {
+                    self.remove_by_id(source, id);
+                }
+            }
+        }
+        if freed.is_empty() {
+            return;
+        }
+        // only entities of `batch` are left in the archetypes with a freed id
+        self.archetypes
+            .retain(|x| !x.component_ids.iter().any(|id| freed.contains(id)));
+        freed.sort();
+        self.relations.free.extend(freed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::component::Component;
+    use std::{alloc::Global, vec::Vec};
+
+    type World = crate::world::World<Global>;
+
+    #[derive(Debug, PartialEq)]
+    struct Likes(u32);
+    impl Component for Likes {}
+
+    struct Targets;
+    impl Component for Targets {}
+
+    #[test]
+    fn relation_pairs() {
+        let mut w = World::new();
+        let [a, b, c] = [w.spawn(), w.spawn(), w.spawn_with((0u8,))];
+        w.add_pair(c, Likes(1), a);
+        w.add_pair(c, Likes(2), b);
+        w.add_pair(b, Likes(3), a);
+        w.add_pair(a, Targets, b);
+
+        assert_eq!(w.get_pair::<Likes>(c, b), Some(&Likes(2)));
+        assert!(!w.has_pair::<Likes>(a, b));
+        assert!(w.has_pair::<Targets>(a, b));
+        assert_eq!(w.targets::<Likes>(c).collect::<Vec<_>>(), [a, b]);
+        assert_eq!(*w.get::<u8>(c).unwrap(), 0);
+
+        // pairs with different targets are different components
+        let pair_a = w.pair_id::<Likes>(a).unwrap();
+        let pair_b = w.pair_id::<Likes>(b).unwrap();
+        assert_ne!(pair_a, pair_b);
+        let archetype = w.archetypes.get_by_entity(c).unwrap();
+        assert!(archetype.contains(&pair_a) && archetype.contains(&pair_b));
+
+        w.get_pair_mut::<Likes>(c, a).unwrap().0 += 10;
+        let mut liking_a = w
+            .query_pair::<Likes>(a)
+            .map(|(e, x)| (e, x.0))
+            .collect::<Vec<_>>();
+        liking_a.sort();
+        assert_eq!(liking_a, [(b, 3), (c, 11)]);
+        assert_eq!(w.query_wildcard::<Likes>().count(), 3);
+
+        w.remove_pair::<Likes>(c, a).unwrap();
+        assert!(w.remove_pair::<Likes>(c, a).is_none());
+        assert_eq!(w.targets::<Likes>(c).collect::<Vec<_>>(), [b]);
+    }
+
+    #[test]
+    fn relation_target_despawned() {
+        let mut w = World::new();
+        let [a, b, c] = [w.spawn(), w.spawn(), w.spawn()];
+        w.add_pair(b, Likes(1), a);
+        w.add_pair(c, Likes(2), a);
+        w.add_pair(c, Likes(3), b);
+
+        w.destroy(a);
+        assert!(!w.has_pair::<Likes>(b, a));
+        assert!(w.pair_id::<Likes>(a).is_none());
+        let wildcard = w
+            .query_wildcard::<Likes>()
+            .map(|(e, t, x)| (e, t, x.0))
+            .collect::<Vec<_>>();
+        assert_eq!(wildcard, [(c, b, 3)]);
+
+        w.despawn_batch(&[b]);
+        assert_eq!(w.query_wildcard::<Likes>().count(), 0);
+    }
+
+    #[test]
+    fn relation_ids_recycled() {
+        let mut w = World::new();
+        let [a, b, c] = [w.spawn(), w.spawn(), w.spawn_with((0u8,))];
+        w.add_pair(b, Likes(1), a);
+        w.add_pair(c, Likes(2), a);
+        let pair = w.pair_id::<Likes>(a).unwrap();
+        let len = w.components.len();
+
+        w.destroy(a);
+        assert!(w.archetypes.iter().all(|x| !x.contains(&pair)));
+        assert_eq!(*w.get::<u8>(c).unwrap(), 0);
+
+        // a pair of another relation gets the freed id
+        let d = w.spawn();
+        w.add_pair(c, Targets, d);
+        assert_eq!(w.pair_id::<Targets>(d), Some(pair));
+        assert_eq!(w.components.len(), len);
+        assert!(w.has_pair::<Targets>(c, d));
+        assert!(!w.has_pair::<Likes>(c, d));
+        assert_eq!(w.query_wildcard::<Likes>().count(), 0);
+    }
+}
@@ -0,0 +1,1027 @@
+use alloc::{string::String, vec::Vec};
+use core::{fmt, ptr::NonNull};
+
+use serde::{
+    de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor},
+    ser::{SerializeSeq, SerializeStruct},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{
+    archetype::{ComponentData, TypeInfo},
+    component::{ComponentId, ComponentInfo},
+    entity::Entity,
+    registry::{DeserializeFn, MapEntitiesFn, SerializeFn, TypeRegistration, TypeRegistry},
+    world::{World, WorldAllocator},
+};
+
+pub use crate::entity::EntityMap;
+
+//a registered type that can be saved and loaded
+#[derive(Clone, Copy)]
+struct Registration {
+    name: &'static str,
+    type_info: TypeInfo,
+    serialize: SerializeFn,
+    deserialize: DeserializeFn,
+    map_entities: Option<MapEntitiesFn>,
+    relation: bool,
+}
+
+impl Registration {
+    fn new(registration: &TypeRegistration) -> Option<Self> {
+        return Some(Self {
+            name: registration.name(),
+            type_info: registration.type_info(),
+            serialize: registration.serialize_fn()?,
+            deserialize: registration.deserialize_fn()?,
+            map_entities: registration.map_entities_fn(),
+            relation: registration.is_relation(),
+        });
+    }
+}
+
+/// Writes the entities of a world along with every component that was
+/// registered with `with_serde` in its type registry.
+///
+/// Relationship pairs are written with their target, when their relation was
+/// registered with `as_relation` as well.
+pub struct WorldSerializer<'a, A>
+where
+    A: WorldAllocator,
+{
+    world: &'a World<A>,
+}
+
+impl<'a, A> WorldSerializer<'a, A>
+where
+    A: WorldAllocator,
+{
+    pub fn new(world: &'a World<A>) -> Self {
+        Self { world }
+    }
+}
+
+impl<'a, A> Serialize for WorldSerializer<'a, A>
+where
+    A: WorldAllocator,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let registry = self.world.type_registry();
+        let mut entities = self.world.entities.iter().copied().collect::<Vec<_>>();
+        entities.sort();
+
+        // pairs are saved with the registration of their relation
+        let registration = |id: ComponentId| {
+            let pair = self.world.relations.pair_of(id);
+            let type_id = self.world.components[id.index()].type_info.type_id();
+            let registration = Registration::new(registry.get(type_id.or(pair.map(|x| x.0))?)?)?;
+            if pair.is_some() && !registration.relation {
+                return None;
+            }
+            return Some((registration, pair.map(|x| x.1)));
+        };
+        let mut archetypes = self
+            .world
+            .archetypes
+            .iter()
+            .filter(|x| !x.empty())
+            .map(|archetype| ArchetypeSerializer {
+                entities: &archetype.entities,
+                columns: archetype
+                    .data
+                    .iter()
+                    .zip(archetype.component_ids.iter())
+                    .filter_map(|(data, x)| {
+                        let (registration, target) = registration(*x)?;
+                        return Some((data, registration, target));
+                    })
+                    .collect(),
+            })
+            .filter(|x| !x.columns.is_empty())
+            .collect::<Vec<_>>();
+        // a sparse set is written like an archetype with a single column
+        archetypes.extend(
+            self.world
+                .sparse
+                .iter_with_ids()
+                .filter(|(_, x)| !x.is_empty())
+                .filter_map(|(id, set)| {
+                    let (registration, target) = registration(id)?;
+                    return Some(ArchetypeSerializer {
+                        entities: &set.entities,
+                        columns: Vec::from([(&set.data, registration, target)]),
+                    });
+                }),
+        );
+
+        let mut state = serializer.serialize_struct("World", 2)?;
+        state.serialize_field("entities", &entities)?;
+        state.serialize_field("archetypes", &archetypes)?;
+        return state.end();
+    }
+}
+
+struct ArchetypeSerializer<'a, A>
+where
+    A: WorldAllocator,
+{
+    entities: &'a [Entity],
+    //the target is set for relationship pairs
+    columns: Vec<(&'a ComponentData<A>, Registration, Option<Entity>)>,
+}
+
+impl<'a, A> Serialize for ArchetypeSerializer<'a, A>
+where
+    A: WorldAllocator,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let components = self
+            .columns
+            .iter()
+            .map(|(_, x, _)| x.name)
+            .collect::<Vec<_>>();
+        let targets = self.columns.iter().map(|(.., x)| *x).collect::<Vec<_>>();
+        let columns = self
+            .columns
+            .iter()
+            .map(|(data, registration, _)| ColumnSerializer {
+                data,
+                len: self.entities.len(),
+                registration,
+            })
+            .collect::<Vec<_>>();
+
+        let mut state = serializer.serialize_struct("Archetype", 4)?;
+        state.serialize_field("components", &components)?;
+        state.serialize_field("entities", self.entities)?;
+        state.serialize_field("columns", &columns)?;
+        state.serialize_field("targets", &targets)?;
+        return state.end();
+    }
+}
+
+struct ColumnSerializer<'a, A>
+where
+    A: WorldAllocator,
+{
+    data: &'a ComponentData<A>,
+    len: usize,
+    registration: &'a Registration,
+}
+
+impl<'a, A> Serialize for ColumnSerializer<'a, A>
+where
+    A: WorldAllocator,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let ty = &self.registration.type_info;
+        let mut seq = serializer.serialize_seq(Some(self.len))?;
+        for i in 0..self.len {
+            let value = unsafe { (self.registration.serialize)(self.data.get(ty, i)) };
+            seq.serialize_element(value)?;
+        }
+        return seq.end();
+    }
+}
+
+/// Loads entities written by `WorldSerializer` into a world.
+///
+/// Every loaded entity gets spawned as a new entity, the returned map points
+/// from the saved entities to the new ones. Everything is read before the
+/// world is touched, so a save that fails to load leaves it as it was. Data
+/// that points at an entity which wasn't saved along with it is an error.
+pub struct WorldDeserializer<'a, A>
+where
+    A: WorldAllocator,
+{
+    world: &'a mut World<A>,
+    registry: TypeRegistry,
+}
+
+impl<'a, A> WorldDeserializer<'a, A>
+where
+    A: WorldAllocator,
+{
+    pub fn new(world: &'a mut World<A>) -> Self {
+        let registry = world.type_registry().clone();
+        Self { world, registry }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum WorldField {
+    Entities,
+    Archetypes,
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum ArchetypeField {
+    Components,
+    Entities,
+    Columns,
+    Targets,
+}
+
+impl<'a, 'de, A> DeserializeSeed<'de> for WorldDeserializer<'a, A>
+where
+    A: WorldAllocator,
+{
+    type Value = EntityMap;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        return deserializer.deserialize_struct("World", &["entities", "archetypes"], self);
+    }
+}
+
+impl<'a, A> WorldDeserializer<'a, A>
+where
+    A: WorldAllocator,
+{
+    //the ids the saved entities get once everything was read, nothing is spawned yet
+    fn reserve<E>(&self, entities: &[Entity]) -> Result<EntityMap, E>
+    where
+        E: de::Error,
+    {
+        let mut map = EntityMap::default();
+        for (i, entity) in entities.iter().enumerate() {
+            let new = Entity(self.world.next_entity + i as u64);
+            if map.insert(*entity, new).is_some() {
+                return Err(E::custom(format_args!(
+                    "entity {:?} is saved twice",
+                    entity
+                )));
+            }
+        }
+        return Ok(map);
+    }
+
+    fn insert_all(
+        self,
+        entities: &[Entity],
+        map: EntityMap,
+        archetypes: Vec<LoadedArchetype<A>>,
+    ) -> EntityMap {
+        for entity in entities {
+            let new = self.world.spawn();
+            debug_assert_eq!(new, map[entity]);
+        }
+        for archetype in archetypes {
+            unsafe { archetype.insert_into(self.world) };
+        }
+        return map;
+    }
+
+    fn archetypes_seed<'s>(&'s self, map: &'s EntityMap) -> ArchetypesSeed<'s, A> {
+        return ArchetypesSeed {
+            registry: &self.registry,
+            allocator: self.world.allocator.clone(),
+            map,
+        };
+    }
+}
+
+impl<'a, 'de, A> Visitor<'de> for WorldDeserializer<'a, A>
+where
+    A: WorldAllocator,
+{
+    type Value = EntityMap;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        return formatter.write_str("a world");
+    }
+
+    fn visit_seq<V>(self, mut seq: V) -> Result<Self::Value, V::Error>
+    where
+        V: SeqAccess<'de>,
+    {
+        let entities = seq
+            .next_element::<Vec<Entity>>()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let map = self.reserve(&entities)?;
+        let archetypes = seq
+            .next_element_seed(self.archetypes_seed(&map))?
+            .ok_or_else(|| de::Error::invalid_length(1, &"a world"))?;
+        return Ok(self.insert_all(&entities, map, archetypes));
+    }
+
+    fn visit_map<V>(self, mut map: V) -> Result<Self::Value, V::Error>
+    where
+        V: MapAccess<'de>,
+    {
+        let mut entities = None;
+        let mut archetypes = Vec::new();
+        while let Some(key) = map.next_key::<WorldField>()? {
+            match key {
+                WorldField::Entities => {
+                    if entities.is_some() {
+                        return Err(de::Error::duplicate_field("entities"));
+                    }
+                    let saved = map.next_value::<Vec<Entity>>()?;
+                    let reserved = self.reserve(&saved)?;
+                    entities = Some((saved, reserved));
+                }
+                WorldField::Archetypes => {
+                    // the entities have to be known before anything can point at them
+                    let Some((_, reserved)) = &entities else {
+                        return Err(de::Error::custom("entities have to come before archetypes"));
+                    };
+                    archetypes.extend(map.next_value_seed(self.archetypes_seed(reserved))?);
+                }
+            }
+        }
+        let (saved, reserved) = entities.ok_or_else(|| de::Error::missing_field("entities"))?;
+        return Ok(self.insert_all(&saved, reserved, archetypes));
+    }
+}
+
+struct ArchetypesSeed<'s, A>
+where
+    A: WorldAllocator,
+{
+    registry: &'s TypeRegistry,
+    allocator: A,
+    map: &'s EntityMap,
+}
+
+impl<'s, 'de, A> DeserializeSeed<'de> for ArchetypesSeed<'s, A>
+where
+    A: WorldAllocator,
+{
+    type Value = Vec<LoadedArchetype<A>>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        return deserializer.deserialize_seq(self);
+    }
+}
+
+impl<'s, 'de, A> Visitor<'de> for ArchetypesSeed<'s, A>
+where
+    A: WorldAllocator,
+{
+    type Value = Vec<LoadedArchetype<A>>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        return formatter.write_str("a list of archetypes");
+    }
+
+    fn visit_seq<V>(self, mut seq: V) -> Result<Self::Value, V::Error>
+    where
+        V: SeqAccess<'de>,
+    {
+        let mut archetypes = Vec::new();
+        while let Some(archetype) = seq.next_element_seed(ArchetypeSeed {
+            registry: self.registry,
+            allocator: self.allocator.clone(),
+            map: self.map,
+        })? {
+            archetypes.push(archetype);
+        }
+        return Ok(archetypes);
+    }
+}
+
+struct ArchetypeSeed<'r, 'm, A>
+where
+    A: WorldAllocator,
+{
+    registry: &'r TypeRegistry,
+    allocator: A,
+    map: &'m EntityMap,
+}
+
+//the rows of one saved archetype, waiting to be inserted
+struct LoadedArchetype<A>
+where
+    A: WorldAllocator,
+{
+    entities: Vec<Entity>,
+    columns: Vec<Column<A>>,
+    //the target of every column that holds a pair
+    targets: Vec<Option<Entity>>,
+}
+
+impl<A> LoadedArchetype<A>
+where
+    A: WorldAllocator,
+{
+    unsafe fn insert_into(mut self, world: &mut World<A>) -> () {
+        let ids = self
+            .columns
+            .iter()
+            .zip(self.targets.iter())
+            .map(|(x, target)| match target {
+                Some(target) => {
+                    let relation = x.type_info.type_id().unwrap();
+                    world.init_pair_by_id(relation, x.type_info.as_pair(), *target)
+                }
+                None => world.init_component(ComponentInfo::dynamic(x.type_info)),
+            })
+            .collect::<Vec<_>>();
+        for (row, entity) in self.entities.iter().enumerate() {
+            let added = ids
+                .iter()
+                .zip(self.columns.iter())
+                .map(|(id, x)| (*id, x.values[row]))
+                .collect::<Vec<_>>();
+            world.relocate(*entity, &added, &[]);
+        }
+        // the values were moved into the world, only the buffers are left
+        for column in self.columns.iter_mut() {
+            for value in column.values.drain(..) {
+                let value = NonNull::new(value).unwrap();
+                column
+                    .allocator
+                    .deallocate(value, column.type_info.layout());
+            }
+        }
+    }
+}
+
+impl<'r, 'm, 'de, A> DeserializeSeed<'de> for ArchetypeSeed<'r, 'm, A>
+where
+    A: WorldAllocator,
+{
+    type Value = LoadedArchetype<A>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        return deserializer.deserialize_struct(
+            "Archetype",
+            &["components", "entities", "columns", "targets"],
+            self,
+        );
+    }
+}
+
+impl<'r, 'm, A> ArchetypeSeed<'r, 'm, A>
+where
+    A: WorldAllocator,
+{
+    fn registrations<E>(&self, names: Vec<String>) -> Result<Vec<Registration>, E>
+    where
+        E: de::Error,
+    {
+        return names
+            .iter()
+            .map(|x| {
+                self.registry
+                    .get_by_name(x)
+                    .and_then(Registration::new)
+                    .ok_or_else(|| E::custom(format_args!("unknown component `{}`", x)))
+            })
+            .collect();
+    }
+
+    fn map_entities<E>(&self, entities: Vec<Entity>) -> Result<Vec<Entity>, E>
+    where
+        E: de::Error,
+    {
+        return entities
+            .iter()
+            .map(|x| {
+                self.map
+                    .get(x)
+                    .copied()
+                    .ok_or_else(|| E::custom(format_args!("unknown entity {:?}", x)))
+            })
+            .collect();
+    }
+
+    fn map_targets<E>(&self, targets: Vec<Option<Entity>>) -> Result<Vec<Option<Entity>>, E>
+    where
+        E: de::Error,
+    {
+        return targets
+            .into_iter()
+            .map(|x| {
+                let target = x.map(|x| {
+                    self.map
+                        .get(&x)
+                        .copied()
+                        .ok_or_else(|| E::custom(format_args!("unknown entity {:?}", x)))
+                });
+                return target.transpose();
+            })
+            .collect();
+    }
+
+    // data without targets was saved before pairs were, so holds none
+    fn loaded<E>(
+        registrations: Option<Vec<Registration>>,
+        entities: Option<Vec<Entity>>,
+        columns: Option<Vec<Column<A>>>,
+        targets: Option<Vec<Option<Entity>>>,
+    ) -> Result<LoadedArchetype<A>, E>
+    where
+        E: de::Error,
+    {
+        let registrations = registrations.ok_or_else(|| E::missing_field("components"))?;
+        let entities = entities.ok_or_else(|| E::missing_field("entities"))?;
+        let columns = columns.ok_or_else(|| E::missing_field("columns"))?;
+        if columns.iter().any(|x| x.values.len() != entities.len()) {
+            return Err(E::custom("every column needs a value per entity"));
+        }
+        let targets = targets.unwrap_or_else(|| Vec::from_iter(columns.iter().map(|_| None)));
+        if targets.len() != columns.len() {
+            return Err(E::custom("every column needs a target"));
+        }
+        // the same component twice would end up in one archetype twice, pairs
+        // of one relation only differ by their target
+        for (i, (registration, target)) in registrations.iter().zip(targets.iter()).enumerate() {
+            if target.is_some() && !registration.relation {
+                return Err(E::custom(format_args!(
+                    "`{}` is not a relation but has a target",
+                    registration.name
+                )));
+            }
+            let twice = registrations[..i]
+                .iter()
+                .zip(targets.iter())
+                .any(|(x, y)| x.name == registration.name && y == target);
+            if twice {
+                return Err(E::custom(format_args!(
+                    "component `{}` is saved twice",
+                    registration.name
+                )));
+            }
+        }
+        return Ok(LoadedArchetype {
+            entities,
+            columns,
+            targets,
+        });
+    }
+}
+
+impl<'r, 'm, 'de, A> Visitor<'de> for ArchetypeSeed<'r, 'm, A>
+where
+    A: WorldAllocator,
+{
+    type Value = LoadedArchetype<A>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        return formatter.write_str("an archetype");
+    }
+
+    fn visit_seq<V>(self, mut seq: V) -> Result<Self::Value, V::Error>
+    where
+        V: SeqAccess<'de>,
+    {
+        let names = seq
+            .next_element::<Vec<String>>()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let registrations = self.registrations(names)?;
+        let entities = seq
+            .next_element::<Vec<Entity>>()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        let entities = self.map_entities(entities)?;
+        let columns = seq.next_element_seed(ColumnsSeed {
+            registrations: &registrations,
+            allocator: self.allocator.clone(),
+            map: self.map,
+        })?;
+        let targets = match seq.next_element::<Vec<Option<Entity>>>()? {
+            Some(x) => Some(self.map_targets(x)?),
+            None => None,
+        };
+        return Self::loaded(Some(registrations), Some(entities), columns, targets);
+    }
+
+    fn visit_map<V>(self, mut map: V) -> Result<Self::Value, V::Error>
+    where
+        V: MapAccess<'de>,
+    {
+        let mut registrations = None;
+        let mut entities = None;
+        let mut columns = None;
+        let mut targets = None;
+        while let Some(key) = map.next_key::<ArchetypeField>()? {
+            match key {
+                ArchetypeField::Components => {
+                    let names = map.next_value::<Vec<String>>()?;
+                    registrations = Some(self.registrations(names)?);
+                }
+                ArchetypeField::Entities => {
+                    let saved = map.next_value::<Vec<Entity>>()?;
+                    entities = Some(self.map_entities(saved)?);
+                }
+                ArchetypeField::Columns => {
+                    // the column types are only known once the components were read
+                    let Some(registrations) = &registrations else {
+                        return Err(de::Error::custom("components have to come before columns"));
+                    };
+                    columns = Some(map.next_value_seed(ColumnsSeed {
+                        registrations: registrations.as_slice(),
+                        allocator: self.allocator.clone(),
+                        map: self.map,
+                    })?);
+                }
+                ArchetypeField::Targets => {
+                    let saved = map.next_value::<Vec<Option<Entity>>>()?;
+                    targets = Some(self.map_targets(saved)?);
+                }
+            }
+        }
+        return Self::loaded(registrations, entities, columns, targets);
+    }
+}
+
+struct ColumnsSeed<'r, 'm, A>
+where
+    A: WorldAllocator,
+{
+    registrations: &'r [Registration],
+    allocator: A,
+    map: &'m EntityMap,
+}
+
+impl<'r, 'm, 'de, A> DeserializeSeed<'de> for ColumnsSeed<'r, 'm, A>
+where
+    A: WorldAllocator,
+{
+    type Value = Vec<Column<A>>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        return deserializer.deserialize_seq(self);
+    }
+}
+
+impl<'r, 'm, 'de, A> Visitor<'de> for ColumnsSeed<'r, 'm, A>
+where
+    A: WorldAllocator,
+{
+    type Value = Vec<Column<A>>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        return formatter.write_str("a column per component");
+    }
+
+    fn visit_seq<V>(self, mut seq: V) -> Result<Self::Value, V::Error>
+    where
+        V: SeqAccess<'de>,
+    {
+        let mut columns = Vec::with_capacity(self.registrations.len());
+        for (i, registration) in self.registrations.iter().enumerate() {
+            let column = seq
+                .next_element_seed(ColumnSeed {
+                    registration,
+                    allocator: self.allocator.clone(),
+                    map: self.map,
+                })?
+                .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+            columns.push(column);
+        }
+        return Ok(columns);
+    }
+}
+
+//values of a single component type, dropped again if loading fails halfway
+struct Column<A>
+where
+    A: WorldAllocator,
+{
+    type_info: TypeInfo,
+    values: Vec<*mut u8>,
+    allocator: A,
+}
+
+impl<A> Drop for Column<A>
+where
+    A: WorldAllocator,
+{
+    fn drop(&mut self) {
+        for value in self.values.drain(..) {
+            unsafe {
+                self.type_info.drop(value);
+                let value = NonNull::new(value).unwrap();
+                self.allocator.deallocate(value, self.type_info.layout());
+            }
+        }
+    }
+}
+
+struct ColumnSeed<'r, 'm, A>
+where
+    A: WorldAllocator,
+{
+    registration: &'r Registration,
+    allocator: A,
+    map: &'m EntityMap,
+}
+
+impl<'r, 'm, 'de, A> DeserializeSeed<'de> for ColumnSeed<'r, 'm, A>
+where
+    A: WorldAllocator,
+{
+    type Value = Column<A>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        return deserializer.deserialize_seq(self);
+    }
+}
+
+impl<'r, 'm, 'de, A> Visitor<'de> for ColumnSeed<'r, 'm, A>
+where
+    A: WorldAllocator,
+{
+    type Value = Column<A>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        return write!(formatter, "a list of `{}`", self.registration.name);
+    }
+
+    fn visit_seq<V>(self, mut seq: V) -> Result<Self::Value, V::Error>
+    where
+        V: SeqAccess<'de>,
+    {
+        let mut column = Column {
+            type_info: self.registration.type_info,
+            values: Vec::with_capacity(seq.size_hint().unwrap_or(0)),
+            allocator: self.allocator.clone(),
+        };
+        while let Some(value) = seq.next_element_seed(ComponentSeed {
+            registration: self.registration,
+            allocator: &self.allocator,
+        })? {
+            // the column drops the value again if it points at an entity that wasn't saved
+            column.values.push(value);
+            if let Some(map_entities) = self.registration.map_entities {
+                let mut missing = None;
+                let mut map = |x: Entity| match self.map.get(&x) {
+                    Some(x) => *x,
+                    None => *missing.get_or_insert(x),
+                };
+                unsafe { map_entities(value, &mut map) };
+                if let Some(x) = missing {
+                    return Err(de::Error::custom(format_args!(
+                        "`{}` points at the unsaved entity {:?}",
+                        self.registration.name, x
+                    )));
+                }
+            }
+        }
+        return Ok(column);
+    }
+}
+
+struct ComponentSeed<'r, 'a, A>
+where
+    A: WorldAllocator,
+{
+    registration: &'r Registration,
+    allocator: &'a A,
+}
+
+impl<'r, 'a, 'de, A> DeserializeSeed<'de> for ComponentSeed<'r, 'a, A>
+where
+    A: WorldAllocator,
+{
+    type Value = *mut u8;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let layout = self.registration.type_info.layout();
+        let value = self
+            .allocator
+            .allocate(layout)
+            .map_err(|_| de::Error::custom("failed to allocate a component"))?
+            .as_ptr() as *mut u8;
+
+        let mut deserializer = <dyn erased_serde::Deserializer>::erase(deserializer);
+        let result = unsafe { (self.registration.deserialize)(&mut deserializer, value) };
+        if let Err(e) = result {
+            unsafe {
+                self.allocator
+                    .deallocate(NonNull::new(value).unwrap(), layout)
+            };
+            return Err(de::Error::custom(e));
+        }
+        return Ok(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WorldDeserializer, WorldSerializer};
+    use crate::{
+        component::{Component, StorageType},
+        entity::{Entity, MapEntities},
+        world::World,
+    };
+    use serde::{de::DeserializeSeed, Deserialize, Serialize};
+    use std::{alloc::Global, string::String};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+    struct Position(f32, f32);
+
+    impl Component for Position {
+        fn name() -> &'static str {
+            return "Position";
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+    struct Target(Entity);
+
+    // kept in a sparse set so saving those is covered too
+    impl Component for Target {
+        const STORAGE: StorageType = StorageType::Sparse;
+        fn name() -> &'static str {
+            return "Target";
+        }
+    }
+
+    impl MapEntities for Target {
+        fn map_entities(&mut self, map: &mut dyn FnMut(Entity) -> Entity) -> () {
+            self.0 = map(self.0);
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Likes(u32);
+
+    impl Component for Likes {
+        fn name() -> &'static str {
+            return "Likes";
+        }
+    }
+
+    struct Unsaved;
+
+    impl Component for Unsaved {}
+
+    fn registered_world() -> World<Global> {
+        let mut world = World::new_in(Global);
+        world.register_type::<Position>().with_serde();
+        world.register_type::<Likes>().with_serde().as_relation();
+        world
+            .register_type::<Target>()
+            .with_serde()
+            .with_map_entities();
+        world
+            .type_registry_mut()
+            .register_as::<String>("String")
+            .with_serde();
+        return world;
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut world = registered_world();
+        let a = world.spawn_with((Position(1.0, 2.0), String::from("a")));
+        let b = world.spawn_with((Position(3.0, 4.0), Target(a)));
+
+        let json = serde_json::to_string(&WorldSerializer::new(&world)).unwrap();
+
+        let mut loaded = registered_world();
+        loaded.spawn_with(Position(0.0, 0.0));
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        let map = WorldDeserializer::new(&mut loaded)
+            .deserialize(&mut deserializer)
+            .unwrap();
+
+        let (new_a, new_b) = (map[&a], map[&b]);
+        assert_ne!(new_a, a);
+        assert_eq!(loaded.get::<Position>(new_a), Some(&Position(1.0, 2.0)));
+        assert_eq!(loaded.get::<String>(new_a).map(|x| x.as_str()), Some("a"));
+        assert_eq!(loaded.get::<Position>(new_b), Some(&Position(3.0, 4.0)));
+        assert_eq!(loaded.get::<Target>(new_b), Some(&Target(new_a)));
+        assert_eq!(loaded.query::<Position>().count(), 3);
+    }
+
+    #[test]
+    fn round_trip_pairs() {
+        let mut world = registered_world();
+        let [a, b] = [world.spawn(), world.spawn_with(Position(0.0, 0.0))];
+        world.add_pair(a, Likes(1), b);
+        world.add_pair(a, Likes(2), a);
+        world.add_pair(b, Likes(3), a);
+
+        let json = serde_json::to_string(&WorldSerializer::new(&world)).unwrap();
+
+        let mut loaded = registered_world();
+        loaded.spawn();
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        let map = WorldDeserializer::new(&mut loaded)
+            .deserialize(&mut deserializer)
+            .unwrap();
+
+        let (a, b) = (map[&a], map[&b]);
+        assert_eq!(loaded.get_pair::<Likes>(a, b), Some(&Likes(1)));
+        assert_eq!(loaded.get_pair::<Likes>(a, a), Some(&Likes(2)));
+        assert_eq!(loaded.get_pair::<Likes>(b, a), Some(&Likes(3)));
+        assert!(!loaded.has::<Likes>(a));
+        assert_eq!(loaded.query_wildcard::<Likes>().count(), 3);
+    }
+
+    #[test]
+    fn unregistered_components_are_skipped() {
+        let mut world = registered_world();
+        world.register_type::<Unsaved>();
+        let a = world.spawn_with((Position(1.0, 2.0), Unsaved));
+        let b = world.spawn_with(Unsaved);
+
+        let json = serde_json::to_string(&WorldSerializer::new(&world)).unwrap();
+
+        let mut loaded = registered_world();
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        let map = WorldDeserializer::new(&mut loaded)
+            .deserialize(&mut deserializer)
+            .unwrap();
+
+        assert_eq!(loaded.get::<Position>(map[&a]), Some(&Position(1.0, 2.0)));
+        assert!(!loaded.has::<Unsaved>(map[&a]));
+        assert!(!loaded.has::<Unsaved>(map[&b]));
+    }
+
+    //loads `json` into `world`, which has to stay as it was if that fails
+    fn load_err(world: &mut World<Global>, json: &str) -> String {
+        let entities = world.entities.len();
+        let positions = world.query::<Position>().count();
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        let result = WorldDeserializer::new(world).deserialize(&mut deserializer);
+        assert_eq!(world.entities.len(), entities);
+        assert_eq!(world.query::<Position>().count(), positions);
+        return std::format!("{}", result.unwrap_err());
+    }
+
+    #[test]
+    fn unknown_component_is_an_error() {
+        let mut world = registered_world();
+        world.spawn_with(Position(1.0, 2.0));
+        world.spawn_with(String::from("a"));
+        let json = serde_json::to_string(&WorldSerializer::new(&world)).unwrap();
+
+        // the positions come first and are not loaded either
+        let mut loaded = World::new_in(Global);
+        loaded.register_type::<Position>().with_serde();
+        loaded.spawn_with(Position(0.0, 0.0));
+        assert!(load_err(&mut loaded, &json).contains("unknown component `String`"));
+    }
+
+    #[test]
+    fn unsaved_entity_is_an_error() {
+        let mut world = registered_world();
+        let a = world.spawn();
+        world.spawn_with((Position(1.0, 2.0),));
+        world.spawn_with((Position(3.0, 4.0), Target(a)));
+        world.destroy(a);
+        let json = serde_json::to_string(&WorldSerializer::new(&world)).unwrap();
+
+        let mut loaded = registered_world();
+        loaded.spawn_with(Position(0.0, 0.0));
+        let error = load_err(&mut loaded, &json);
+        assert!(error.contains("`Target` points at the unsaved entity"));
+    }
+
+    #[test]
+    fn malformed_saves_are_errors() {
+        let mut world = registered_world();
+        world.spawn_with(Position(0.0, 0.0));
+        let cases = [
+            (
+                r#"{"entities":[0],"archetypes":[{"components":["Position","Position"],"entities":[0],"columns":[[[1.0,2.0]],[[3.0,4.0]]]}]}"#,
+                "component `Position` is saved twice",
+            ),
+            (
+                r#"{"entities":[0,1],"archetypes":[{"components":["Position"],"entities":[0],"columns":[[[1.0,2.0]]],"targets":[1]}]}"#,
+                "`Position` is not a relation",
+            ),
+            (
+                r#"{"entities":[0,1,0],"archetypes":[]}"#,
+                "entity Entity(0) is saved twice",
+            ),
+        ];
+        for (json, expected) in cases {
+            assert!(load_err(&mut world, json).contains(expected));
+        }
+    }
+}
@@ -0,0 +1,463 @@
+use alloc::{vec, vec::Vec};
+use core::{
+    alloc::{Allocator, Layout},
+    any::TypeId,
+    ptr,
+};
+
+use rustc_hash::FxHashSet;
+
+use crate::{
+    archetype::{ArchetypeSet, ComponentData, TypeInfo},
+    component::{ComponentId, ComponentInfo},
+    entity::Entity,
+    hierarchy::{clone_children, Children, Parent},
+    registry::CloneFn,
+    relation::Relations,
+    world::{World, WorldAllocator, WorldId},
+};
+
+//the saved values of one component, `clone` is none for bytewise copies
+struct Column<A>
+where
+    A: Allocator,
+{
+    id: ComponentId,
+    type_info: TypeInfo,
+    clone: Option<CloneFn>,
+    data: ComponentData<A>,
+}
+
+struct Table<A>
+where
+    A: Allocator,
+{
+    entities: Vec<Entity>,
+    columns: Vec<Column<A>>,
+}
+
+/// The entities and components of a world at one point in time.
+///
+/// It refers to components by id, so it can only be restored into the world
+/// that took it.
+pub struct Snapshot<A>
+where
+    A: Allocator,
+{
+    world: WorldId,
+    //how many component ids had been handed out
+    components: usize,
+    next_entity: u64,
+    entities: FxHashSet<Entity>,
+    relations: Relations,
+    //the metadata of every pair, their ids get reused once a target is gone
+    pairs: Vec<(ComponentId, TypeInfo)>,
+    //every archetype in order, empty ones included
+    tables: Vec<Table<A>>,
+    //one single column table per sparse set
+    sparse: Vec<Table<A>>,
+    allocator: A,
+}
+
+impl<A> Snapshot<A>
+where
+    A: Allocator,
+{
+    pub fn len(&self) -> usize {
+        return self.entities.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.entities.is_empty();
+    }
+}
+
+impl<A> Drop for Snapshot<A>
+where
+    A: Allocator,
+{
+    fn drop(&mut self) {
+        for table in self.tables.iter().chain(self.sparse.iter()) {
+            for column in table.columns.iter() {
+                let layout = array_layout(&column.type_info, table.entities.len());
+                unsafe {
+                    for i in 0..table.entities.len() {
+                        column.type_info.drop(column.data.get(&column.type_info, i));
+                    }
+                    ptr::read(&column.data).dealloc(&layout, &self.allocator);
+                }
+            }
+        }
+    }
+}
+
+fn array_layout(type_info: &TypeInfo, len: usize) -> Layout {
+    let layout = type_info.layout();
+    return Layout::from_size_align(layout.size() * len, layout.align()).unwrap();
+}
+
+// a single memcpy for types that are `Copy`
+unsafe fn clone_values(
+    src: *const u8,
+    dst: *mut u8,
+    len: usize,
+    type_info: &TypeInfo,
+    clone: Option<CloneFn>,
+) -> () {
+    let size = type_info.layout().size();
+    match clone {
+        None => ptr::copy_nonoverlapping(src, dst, size * len),
+        Some(clone) => {
+            for i in 0..len {
+                clone(src.add(i * size), dst.add(i * size));
+            }
+        }
+    }
+}
+
+impl<A> World<A>
+where
+    A: WorldAllocator,
+{
+    /// Saves every entity along with its components and the next entity id.
+    ///
+    /// Every component has to be registered with `with_clone` or `with_copy`,
+    /// the ones registered with `with_copy` are saved a column at a time.
+    pub fn snapshot(&self) -> Snapshot<A> {
+        let tables = self
+            .archetypes
+            .iter()
+            .map(|archetype| {
+                let columns = (0..archetype.component_ids.len())
+                    .map(|i| unsafe {
+                        let src = archetype.data[i].as_ptr();
+                        let ty = &archetype.type_info[i];
+                        return self.save_column(
+                            archetype.component_ids[i],
+                            ty,
+                            src,
+                            archetype.len(),
+                        );
+                    })
+                    .collect();
+                return Table {
+                    entities: archetype.entities.clone(),
+                    columns,
+                };
+            })
+            .collect();
+        let sparse = self
+            .sparse
+            .iter_with_ids()
+            .filter(|(_, x)| !x.is_empty())
+            .map(|(id, set)| {
+                let src = unsafe { set.data.as_ptr() };
+                return Table {
+                    entities: set.entities.clone(),
+                    columns: vec![self.save_column(id, set.type_info(), src, set.len())],
+                };
+            })
+            .collect();
+        return Snapshot {
+            world: self.id,
+            components: self.components.len(),
+            next_entity: self.next_entity,
+            entities: self.entities.clone(),
+            relations: self.relations.clone(),
+            pairs: self
+                .relations
+                .ids()
+                .map(|x| (x, self.components[x.index()].type_info))
+                .collect(),
+            tables,
+            sparse,
+            allocator: self.allocator.clone(),
+        };
+    }
+
+    /// Puts the world back into the state `snapshot` was taken in.
+    ///
+    /// Entities spawned since are gone and new ones get the same ids they got
+    /// after the snapshot was taken. The archetypes are put back too, so
+    /// replaying the same changes ends up in the same order. Components keep
+    /// their ids, hooks and observers, only the ids of pairs added since are
+    /// handed out again. Hooks and observers don't run.
+    ///
+    /// Panics if `snapshot` was taken of another world.
+    pub fn restore(&mut self, snapshot: &Snapshot<A>) -> () {
+        assert_eq!(
+            snapshot.world, self.id,
+            "the snapshot was taken of another world"
+        );
+        self.archetypes = ArchetypeSet::new();
+        self.sparse.iter_mut().for_each(|x| x.clear());
+        self.next_entity = snapshot.next_entity;
+        self.entities = snapshot.entities.clone();
+        // pairs added since may target entities that are gone now
+        let added = self
+            .relations
+            .all_ids()
+            .filter(|x| x.index() >= snapshot.components)
+            .collect();
+        self.relations = snapshot.relations.clone();
+        self.relations.release(added);
+        for (id, type_info) in snapshot.pairs.iter() {
+            self.components[id.index()] = ComponentInfo::dynamic(*type_info);
+        }
+
+        for table in snapshot.tables.iter() {
+            let ids = table.columns.iter().map(|x| x.id).collect::<Vec<_>>();
+            let type_info = table
+                .columns
+                .iter()
+                .map(|x| x.type_info)
+                .collect::<Vec<_>>();
+            unsafe {
+                let dst = self.archetypes.push_uninit(
+                    &ids,
+                    &type_info,
+                    self.allocator.clone(),
+                    &table.entities,
+                );
+                for (column, dst) in table.columns.iter().zip(dst) {
+                    let src = column.data.as_ptr();
+                    clone_values(
+                        src,
+                        dst,
+                        table.entities.len(),
+                        &column.type_info,
+                        column.clone,
+                    );
+                }
+            }
+        }
+
+        for table in snapshot.sparse.iter() {
+            let column = &table.columns[0];
+            let ty = column.type_info;
+            let set = self
+                .sparse
+                .get_or_add(column.id, ty, self.allocator.clone());
+            // values get cloned into `value` and moved into the set from there
+            unsafe {
+                let value = ComponentData::new(ty.layout(), 1, &self.allocator);
+                for (i, entity) in table.entities.iter().enumerate() {
+                    clone_values(
+                        column.data.get(&ty, i),
+                        value.as_ptr(),
+                        1,
+                        &ty,
+                        column.clone,
+                    );
+                    set.insert(*entity, value.as_ptr());
+                }
+                value.dealloc(&ty.layout(), &self.allocator);
+            }
+        }
+    }
+
+    fn save_column(&self, id: ComponentId, ty: &TypeInfo, src: *const u8, len: usize) -> Column<A> {
+        // empty archetypes are only saved for their order and need no clone fn
+        let clone = match len {
+            0 => None,
+            _ => self.snapshot_clone(id),
+        };
+        unsafe {
+            let data = ComponentData::new(ty.layout(), len, &self.allocator);
+            clone_values(src, data.as_ptr(), len, ty, clone);
+            return Column {
+                id,
+                type_info: *ty,
+                clone,
+                data,
+            };
+        }
+    }
+
+    //how the values of a component get saved, none copies them bytewise
+    fn snapshot_clone(&self, id: ComponentId) -> Option<CloneFn> {
+        let type_id = self.components[id.index()].type_info.type_id();
+        // the hierarchy is saved without having to be registered
+        if type_id == Some(TypeId::of::<Parent>()) {
+            return None;
+        }
+        if type_id == Some(TypeId::of::<Children>()) {
+            return Some(clone_children);
+        }
+        if self.registration_of(id).is_some_and(|x| x.is_copy()) {
+            return None;
+        }
+        return Some(self.clone_fn(id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        component::{Component, StorageType},
+        entity::Entity,
+    };
+    use core::alloc::Layout;
+    use std::{alloc::Global, rc::Rc, string::String, vec::Vec};
+
+    type World = crate::world::World<Global>;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Selected;
+    impl Component for Selected {
+        const STORAGE: StorageType = StorageType::Sparse;
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Likes;
+    impl Component for Likes {}
+
+    #[test]
+    fn snapshot_restore() {
+        let mut w = World::new();
+        w.register_type::<u32>().with_copy();
+        w.register_type::<String>().with_clone();
+        w.register_type::<Selected>().with_copy();
+        w.register_type::<Likes>().with_copy();
+        w.register_type::<Rc<()>>().with_clone();
+        let rc = Rc::new(());
+        let a = w.spawn_with((1u32, String::from("a"), Selected));
+        let b = w.spawn_with((2u32, rc.clone()));
+        w.set_parent(b, a);
+        w.add_pair(a, Likes, b);
+
+        let snapshot = w.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(Rc::strong_count(&rc), 3);
+
+        *w.get_mut::<u32>(a).unwrap() = 10;
+        w.get_mut::<String>(a).unwrap().push('!');
+        w.remove::<(Selected,)>(a);
+        w.destroy(b);
+        let c = w.spawn_with((3u32,));
+        assert_eq!(Rc::strong_count(&rc), 2);
+
+        for _ in 0..2 {
+            w.restore(&snapshot);
+            assert_eq!(*w.get::<u32>(a).unwrap(), 1);
+            assert_eq!(w.get::<String>(a).unwrap(), "a");
+            assert!(w.has::<Selected>(a));
+            assert_eq!(w.children(a), [b]);
+            assert!(w.has_pair::<Likes>(a, b));
+            assert_eq!(Rc::strong_count(&rc), 3);
+            assert!(!w.has::<u32>(c));
+            assert_eq!(w.query::<u32>().count(), 2);
+            // ids are handed out like they were after the snapshot
+            assert_eq!(w.spawn(), c);
+        }
+
+        drop(snapshot);
+        assert_eq!(Rc::strong_count(&rc), 2);
+    }
+
+    fn setup(w: &mut World) -> Entity {
+        w.register_type::<u32>().with_copy();
+        w.register_type::<Likes>().with_copy();
+        w.spawn_with((7u32,));
+        return w.spawn_with((0u32,));
+    }
+
+    fn replay(w: &mut World, a: Entity) -> Entity {
+        w.spawn_with((1u32, 1u64));
+        w.spawn_with((2u32, true));
+        let b = w.spawn_with((3u32,));
+        w.add_pair(a, Likes, b);
+        return b;
+    }
+
+    #[test]
+    fn restore_replay() {
+        let mut w = World::new();
+        let a = setup(&mut w);
+        let snapshot = w.snapshot();
+        // a mispredicted frame making archetypes and pairs the replay makes
+        // differently
+        let c = w.spawn_with((9u32, true));
+        w.spawn_with((8u32, 1u16));
+        w.add_pair(a, Likes, c);
+        let mispredicted = w.pair_id::<Likes>(c);
+        w.restore(&snapshot);
+        assert!(w.pair_id::<Likes>(c).is_none());
+        let b = replay(&mut w, a);
+
+        let mut fresh = World::new();
+        let fresh_a = setup(&mut fresh);
+        let fresh_b = replay(&mut fresh, fresh_a);
+        assert_eq!((a, b), (fresh_a, fresh_b));
+        // the id of the forgotten pair is handed out again
+        assert_eq!(w.pair_id::<Likes>(b), mispredicted);
+        let order = |w: &World| w.query::<u32>().copied().collect::<Vec<_>>();
+        assert_eq!(order(&w), order(&fresh));
+        assert_eq!(order(&w), [7, 3, 1, 2, 0]);
+    }
+
+    #[test]
+    fn restore_keeps_components() {
+        use crate::observer::OnAdd;
+        use core::cell::Cell;
+
+        let mut w = World::new();
+        w.register_type::<u32>().with_copy();
+        w.register_type::<Selected>().with_copy();
+        let snapshot = w.snapshot();
+        let dynamic = w.register_dynamic_component("Mana", Layout::new::<u32>(), None);
+        let added = Rc::new(Cell::new(0));
+        let count = added.clone();
+        w.observe::<OnAdd<Selected>, _>(move |_, _| count.set(count.get() + 1));
+        let prefab = w.prefab((Selected,));
+        w.spawn_with((1u32, Selected));
+        let id = w.component_id::<Selected>();
+
+        w.restore(&snapshot);
+        assert_eq!(w.component_id::<Selected>(), id);
+        assert_eq!(w.query::<Selected>().count(), 0);
+        // observers of components first used since still run
+        w.spawn_with((Selected,));
+        w.instantiate(&prefab);
+        assert_eq!(added.get(), 3);
+        let a = w.spawn();
+        unsafe { w.insert_by_id(a, dynamic, &7u32 as *const u32 as *const u8) };
+        assert_eq!(
+            unsafe { *w.get_by_id(a, dynamic).unwrap().cast::<u32>() },
+            7
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "taken of another world")]
+    fn restore_other_world() {
+        let mut w = World::new();
+        setup(&mut w);
+        let snapshot = w.snapshot();
+        let mut other = World::new();
+        setup(&mut other);
+        other.restore(&snapshot);
+    }
+
+    #[test]
+    #[should_panic(expected = "components the world forgot")]
+    fn restore_forgets_pairs() {
+        let mut w = World::new();
+        w.register_type::<Likes>().with_copy();
+        let a = w.spawn();
+        let snapshot = w.snapshot();
+        let b = w.spawn();
+        w.add_pair(a, Likes, b);
+        let prefab = w.prefab_of(a).unwrap();
+        w.restore(&snapshot);
+        w.instantiate(&prefab);
+    }
+
+    #[test]
+    #[should_panic(expected = "register it with `with_clone`")]
+    fn snapshot_unregistered() {
+        let mut w = World::new();
+        w.spawn_with((1u8,));
+        w.snapshot();
+    }
+}
@@ -0,0 +1,295 @@
+use core::{
+    alloc::{Allocator, Layout},
+    ptr,
+};
+
+use alloc::vec::Vec;
+
+use crate::{
+    archetype::{ComponentData, TypeInfo},
+    component::ComponentId,
+    entity::Entity,
+};
+
+//marks an entity index without a component in `sparse`
+const EMPTY: u32 = u32::MAX;
+
+/// Components of a single type kept apart from the archetype tables.
+///
+/// Adding or removing one doesn't move the other components of an entity,
+/// which suits components that get toggled a lot.
+pub struct SparseSet<A>
+where
+    A: Allocator,
+{
+    type_info: TypeInfo,
+    //position in the dense arrays by entity index
+    sparse: Vec<u32>,
+    pub(crate) entities: Vec<Entity>,
+    pub(crate) data: ComponentData<A>,
+    capacity: usize,
+    allocator: A,
+}
+
+impl<A> SparseSet<A>
+where
+    A: Allocator,
+{
+    pub fn new(type_info: TypeInfo, allocator: A) -> Self {
+        let capacity = 16;
+        Self {
+            type_info,
+            sparse: Vec::new(),
+            entities: Vec::new(),
+            data: unsafe { ComponentData::new(type_info.layout(), capacity, &allocator) },
+            capacity,
+            allocator,
+        }
+    }
+
+    pub fn type_info(&self) -> &TypeInfo {
+        return &self.type_info;
+    }
+
+    pub(crate) fn type_info_mut(&mut self) -> &mut TypeInfo {
+        return &mut self.type_info;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.entities.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.entities.is_empty();
+    }
+
+    fn index_of(&self, entity: Entity) -> Option<usize> {
+        let index = *self.sparse.get(entity.0 as usize)?;
+        if index == EMPTY {
+            return None;
+        }
+        return Some(index as usize);
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        return self.index_of(entity).is_some();
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<*mut u8> {
+        let index = self.index_of(entity)?;
+        return Some(unsafe { self.data.get(&self.type_info, index) });
+    }
+
+    /// Moves the value behind `data` in, dropping the one `entity` had before.
+    pub unsafe fn insert(&mut self, entity: Entity, data: *mut u8) -> () {
+        if let Some(old) = self.get(entity) {
+            self.type_info.drop(old);
+            ptr::copy_nonoverlapping(data, old, self.type_info.layout().size());
+            return;
+        }
+
+        if self.len() >= self.capacity {
+            let new_size = (self.capacity * 2).max(1);
+            let layout = self.type_info.layout();
+            self.data
+                .grow(&layout, self.capacity, new_size, &self.allocator);
+            self.capacity = new_size;
+        }
+        let slot = entity.0 as usize;
+        if slot >= self.sparse.len() {
+            self.sparse.resize(slot + 1, EMPTY);
+        }
+        self.sparse[slot] = self.len() as u32;
+        self.data.set(&self.type_info, self.len(), data);
+        self.entities.push(entity);
+    }
+
+    //drops the component of `entity`, false if it didn't have one
+    pub fn remove(&mut self, entity: Entity) -> bool {
+        let Some(data) = self.get(entity) else {
+            return false;
+        };
+        unsafe {
+            self.type_info.drop(data);
+            return self.forget(entity);
+        }
+    }
+
+    /// Like `remove` but without dropping, the value must have been moved out.
+    pub unsafe fn forget(&mut self, entity: Entity) -> bool {
+        let Some(index) = self.index_of(entity) else {
+            return false;
+        };
+        let last = self.len() - 1;
+        // fill the hole with the last value so the dense arrays stay packed
+        if index != last {
+            let removed = self.data.get(&self.type_info, index);
+            let moved = self.data.get(&self.type_info, last);
+            ptr::copy_nonoverlapping(moved, removed, self.type_info.layout().size());
+            self.sparse[self.entities[last].0 as usize] = index as u32;
+        }
+        self.sparse[entity.0 as usize] = EMPTY;
+        self.entities.swap_remove(index);
+        return true;
+    }
+
+    pub fn clear(&mut self) -> () {
+        for i in 0..self.len() {
+            unsafe { self.type_info.drop(self.data.get(&self.type_info, i)) };
+        }
+        self.entities.clear();
+        self.sparse.clear();
+    }
+
+    pub fn entity_iter(&self) -> impl Iterator<Item = &Entity> {
+        return self.entities.iter();
+    }
+}
+
+impl<A> Drop for SparseSet<A>
+where
+    A: Allocator,
+{
+    fn drop(&mut self) {
+        self.clear();
+        let layout = self.type_info.layout();
+        let layout =
+            Layout::from_size_align(layout.size() * self.capacity, layout.align()).unwrap();
+        // the set is gone after this, so the buffer can be taken out
+        unsafe { ptr::read(&self.data).dealloc(&layout, &self.allocator) };
+    }
+}
+
+/// The sparse sets of a world, indexed by `ComponentId`.
+pub struct SparseSets<A>
+where
+    A: Allocator,
+{
+    sets: Vec<Option<SparseSet<A>>>,
+}
+
+impl<A> SparseSets<A>
+where
+    A: Allocator,
+{
+    pub fn new() -> Self {
+        Self { sets: Vec::new() }
+    }
+
+    pub fn get(&self, id: ComponentId) -> Option<&SparseSet<A>> {
+        return self.sets.get(id.index())?.as_ref();
+    }
+
+    pub fn get_mut(&mut self, id: ComponentId) -> Option<&mut SparseSet<A>> {
+        return self.sets.get_mut(id.index())?.as_mut();
+    }
+
+    pub fn get_or_add(
+        &mut self,
+        id: ComponentId,
+        type_info: TypeInfo,
+        allocator: A,
+    ) -> &mut SparseSet<A> {
+        if id.index() >= self.sets.len() {
+            self.sets.resize_with(id.index() + 1, || None);
+        }
+        return self.sets[id.index()].get_or_insert_with(|| SparseSet::new(type_info, allocator));
+    }
+
+    //drops every component `entity` has in a sparse set
+    pub fn remove_entity(&mut self, entity: Entity) -> () {
+        self.iter_mut().for_each(|x| {
+            x.remove(entity);
+        });
+    }
+
+    pub unsafe fn forget_entity(&mut self, entity: Entity) -> () {
+        self.iter_mut().for_each(|x| {
+            x.forget(entity);
+        });
+    }
+
+    //the components of `entity` that live in a sparse set
+    pub fn ids_of(&self, entity: Entity) -> impl Iterator<Item = ComponentId> + '_ {
+        return self
+            .sets
+            .iter()
+            .enumerate()
+            .filter(move |(_, x)| x.as_ref().is_some_and(|x| x.contains(entity)))
+            .map(|(i, _)| ComponentId(i as u32));
+    }
+
+    pub fn iter_with_ids(&self) -> impl Iterator<Item = (ComponentId, &SparseSet<A>)> {
+        return self
+            .sets
+            .iter()
+            .enumerate()
+            .filter_map(|(i, x)| Some((ComponentId(i as u32), x.as_ref()?)));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SparseSet<A>> {
+        return self.sets.iter().flatten();
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut SparseSet<A>> {
+        return self.sets.iter_mut().flatten();
+    }
+}
+
+impl<A> Default for SparseSets<A>
+where
+    A: Allocator,
+{
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SparseSet;
+    use crate::{archetype::TypeInfo, entity::Entity};
+    use std::{alloc::Global, rc::Rc};
+
+    #[test]
+    fn sparse_set_insert_remove() {
+        let mut set = SparseSet::new(TypeInfo::new::<u32>(), Global);
+        for i in 0..40u32 {
+            let mut value = i * 10;
+            unsafe { set.insert(Entity(i as u64 * 3), &mut value as *mut u32 as *mut u8) };
+        }
+        assert_eq!(set.len(), 40);
+        assert!(set.remove(Entity(0)));
+        assert!(!set.remove(Entity(0)));
+        assert!(!set.remove(Entity(1)));
+        assert!(!set.contains(Entity(0)));
+
+        // the last value took the place of the removed one
+        let value = set.get(Entity(39 * 3)).unwrap();
+        assert_eq!(unsafe { *(value as *mut u32) }, 390);
+        let value = set.get(Entity(3)).unwrap();
+        assert_eq!(unsafe { *(value as *mut u32) }, 10);
+        assert_eq!(set.len(), 39);
+    }
+
+    #[test]
+    fn sparse_set_drop() {
+        let rc = Rc::new(());
+        let mut set = SparseSet::new(TypeInfo::new::<Rc<()>>(), Global);
+        for i in 0..3 {
+            let mut value = rc.clone();
+            unsafe { set.insert(Entity(i), &mut value as *mut Rc<()> as *mut u8) };
+            core::mem::forget(value);
+        }
+        // replacing drops the old value
+        let mut value = rc.clone();
+        unsafe { set.insert(Entity(1), &mut value as *mut Rc<()> as *mut u8) };
+        core::mem::forget(value);
+        assert_eq!(Rc::strong_count(&rc), 4);
+
+        set.remove(Entity(0));
+        assert_eq!(Rc::strong_count(&rc), 3);
+        drop(set);
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+}
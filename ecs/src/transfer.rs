@@ -0,0 +1,339 @@
+use alloc::vec::Vec;
+use core::any::TypeId;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{
+    component::{ComponentId, ComponentInfo},
+    entity::{Entity, EntityMap, MapEntities},
+    hierarchy::{Children, Parent},
+    registry::MapEntitiesFn,
+    world::{World, WorldAllocator},
+};
+
+unsafe fn map_entities<T: MapEntities>(ptr: *mut u8, map: &mut dyn FnMut(Entity) -> Entity) -> () {
+    (*ptr.cast::<T>()).map_entities(map);
+}
+
+impl<A> World<A>
+where
+    A: WorldAllocator,
+{
+    /// Moves `entity` and its components into `other` and returns its id there.
+    ///
+    /// The values are moved as they are, nothing gets cloned or dropped and no
+    /// hooks or observers run. Links to entities that stay behind are cut first:
+    /// the entity leaves its parent, its children stay here as roots and pairs
+    /// targeting entities of this world get removed.
+    pub fn transfer<B>(&mut self, entity: Entity, other: &mut World<B>) -> Entity
+    where
+        B: WorldAllocator,
+    {
+        let map = self.move_entities(&[entity], other);
+        return *map
+            .get(&entity)
+            .expect("only entities of the world can be transferred");
+    }
+
+    /// Moves every entity of `other` into this world, leaving `other` empty.
+    ///
+    /// Returns where each entity of `other` ended up, references between the
+    /// moved entities are pointed at the new ids.
+    pub fn append<B>(&mut self, other: &mut World<B>) -> EntityMap
+    where
+        B: WorldAllocator,
+    {
+        let entities = other.entities.iter().copied().collect::<Vec<_>>();
+        return other.move_entities(&entities, self);
+    }
+
+    fn move_entities<B>(&mut self, entities: &[Entity], other: &mut World<B>) -> EntityMap
+    where
+        B: WorldAllocator,
+    {
+        // sorted so the new ids follow the order of the old ones
+        let mut batch = entities
+            .iter()
+            .copied()
+            .filter(|x| self.entities.contains(x))
+            .collect::<Vec<_>>();
+        batch.sort();
+        batch.dedup();
+        let set = batch.iter().copied().collect::<FxHashSet<_>>();
+        self.cut_links(&set);
+
+        let map = batch
+            .iter()
+            .map(|x| (*x, other.spawn()))
+            .collect::<EntityMap>();
+        // every id is looked up before anything moves, so a mismatch can't
+        // leave values in both worlds
+        let mut ids = FxHashMap::default();
+        for entity in batch.iter() {
+            for id in self.ids_of(*entity) {
+                ids.entry(id)
+                    .or_insert_with(|| self.counterpart(id, other, &map));
+            }
+        }
+        for entity in batch {
+            unsafe { self.move_entity(entity, other, &map, &ids) };
+        }
+        self.remove_targets(&set);
+        self.flush();
+        return map;
+    }
+
+    // leaves only references to entities in `batch` behind
+    fn cut_links(&mut self, batch: &FxHashSet<Entity>) -> () {
+        for entity in batch.iter().copied() {
+            if self.parent(entity).is_some_and(|x| !batch.contains(&x)) {
+                self.remove_parent(entity);
+            }
+            let children = self.children(entity).to_vec();
+            for child in children.into_iter().filter(|x| !batch.contains(x)) {
+                self.remove_parent(child);
+            }
+            let pairs = self
+                .ids_of(entity)
+                .into_iter()
+                .filter(|x| {
+                    let target = self.relations.pair_of(*x).map(|(_, x)| x);
+                    return target.is_some_and(|x| !batch.contains(&x));
+                })
+                .collect::<Vec<_>>();
+            for id in pairs {
+                self.remove_by_id(entity, id);
+            }
+        }
+    }
+
+    //`ids` holds the id every component of this world has in `other`
+    unsafe fn move_entity<B>(
+        &mut self,
+        entity: Entity,
+        other: &mut World<B>,
+        map: &EntityMap,
+        ids: &FxHashMap<ComponentId, ComponentId>,
+    ) -> ()
+    where
+        B: WorldAllocator,
+    {
+        let new = map[&entity];
+        let mut remap = |x: Entity| map.get(&x).copied().unwrap_or(x);
+        let mut table = Vec::new();
+        for id in self.ids_of(entity) {
+            let data = self.get_mut_by_id(entity, id).unwrap();
+            if let Some(map_entities) = self.map_entities_fn(id) {
+                map_entities(data, &mut remap);
+            }
+            let other_id = ids[&id];
+            // the storage is up to the other world
+            if other.is_sparse(other_id) {
+                let type_info = other.components[other_id.index()].type_info;
+                other
+                    .sparse
+                    .get_or_add(other_id, type_info, other.allocator.clone())
+                    .insert(new, data);
+            } else {
+                table.push((other_id, data));
+            }
+        }
+
+        if self.archetypes.get_by_entity(entity).is_some() || !table.is_empty() {
+            table.sort_by_key(|(x, _)| *x);
+            let table_ids = table.iter().map(|(x, _)| *x).collect::<Vec<_>>();
+            let data = table.iter().map(|(_, x)| *x).collect::<Vec<_>>();
+            let (_, type_info) = other.sorted_components(&table_ids);
+            other.archetypes.add_entity(
+                &table_ids,
+                &type_info,
+                other.allocator.clone(),
+                new,
+                &data,
+            );
+        }
+        self.archetypes.forget(entity);
+        self.sparse.forget_entity(entity);
+        self.entities.remove(&entity);
+    }
+
+    //the id `other` uses for the component `id` of this world
+    fn counterpart<B>(&self, id: ComponentId, other: &mut World<B>, map: &EntityMap) -> ComponentId
+    where
+        B: WorldAllocator,
+    {
+        let type_info = self.components[id.index()].type_info;
+        if let Some((relation, target)) = self.relations.pair_of(id) {
+            return other.init_pair_by_id(relation, type_info, map[&target]);
+        }
+        if type_info.type_id().is_some() {
+            return other.init_component(ComponentInfo::dynamic(type_info));
+        }
+        // components without a rust type are matched by name, pairs have no name of their own
+        let found = (0..other.components.len())
+            .map(|x| ComponentId(x as u32))
+            .find(|x| {
+                let info = other.components[x.index()].type_info;
+                return info.type_id().is_none()
+                    && info.name() == type_info.name()
+                    && other.relations.pair_of(*x).is_none();
+            });
+        let Some(found) = found else {
+            return other.push_component(ComponentInfo::dynamic(type_info));
+        };
+        assert_eq!(
+            other.components[found.index()].type_info.layout(),
+            type_info.layout(),
+            "the component `{}` has another layout in the other world",
+            type_info.name()
+        );
+        return found;
+    }
+
+    fn map_entities_fn(&self, id: ComponentId) -> Option<MapEntitiesFn> {
+        let type_id = self.components[id.index()].type_info.type_id()?;
+        if type_id == TypeId::of::<Parent>() {
+            return Some(map_entities::<Parent>);
+        }
+        if type_id == TypeId::of::<Children>() {
+            return Some(map_entities::<Children>);
+        }
+        return self.type_registration(type_id)?.map_entities_fn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        component::{Component, StorageType},
+        entity::{Entity, MapEntities},
+    };
+    use core::{
+        alloc::{AllocError, Allocator, Layout},
+        cell::Cell,
+        ptr::NonNull,
+    };
+    use std::{alloc::Global, rc::Rc, string::String, vec::Vec};
+
+    type World = crate::world::World<Global>;
+
+    // counts the allocations it hands out
+    #[derive(Clone, Default)]
+    struct Counting(Rc<Cell<usize>>);
+
+    unsafe impl Allocator for Counting {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            self.0.set(self.0.get() + 1);
+            return Global.allocate(layout);
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            Global.deallocate(ptr, layout);
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Selected;
+    impl Component for Selected {
+        const STORAGE: StorageType = StorageType::Sparse;
+    }
+
+    struct Likes;
+    impl Component for Likes {}
+
+    struct Follows(Entity);
+    impl Component for Follows {}
+    impl MapEntities for Follows {
+        fn map_entities(&mut self, map: &mut dyn FnMut(Entity) -> Entity) -> () {
+            self.0 = map(self.0);
+        }
+    }
+
+    #[test]
+    fn transfer_entity() {
+        let mut w = World::new();
+        let counting = Counting::default();
+        let mut other = crate::world::World::new_in(counting.clone());
+        other.spawn();
+        let rc = Rc::new(());
+        let parent = w.spawn();
+        let a = w.spawn_with((rc.clone(), String::from("a"), Selected));
+        let b = w.spawn_with((1u32,));
+        w.set_parent(a, parent);
+        w.add_pair(a, Likes, b);
+
+        let moved = w.transfer(a, &mut other);
+        assert!(!w.has::<String>(a));
+        assert!(!w.has::<Selected>(a));
+        assert!(w.children(parent).is_empty());
+        assert_eq!(other.get::<String>(moved).unwrap(), "a");
+        assert_eq!(other.get::<Selected>(moved), Some(&Selected));
+        assert!(other.parent(moved).is_none());
+        assert_eq!(other.targets::<Likes>(moved).count(), 0);
+        // moved, not cloned
+        assert_eq!(Rc::strong_count(&rc), 2);
+        assert!(counting.0.get() > 0);
+        drop(other);
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+
+    #[test]
+    fn transfer_dynamic() {
+        unsafe fn drop_a(_: *mut u8) -> () {}
+        unsafe fn drop_b(_: *mut u8) -> () {}
+
+        let mut w = World::new();
+        let health = w.register_dynamic_component("Health", Layout::new::<u32>(), Some(drop_a));
+        let mut other = World::new();
+        other.register_dynamic_component("Mana", Layout::new::<u32>(), None);
+        // the same component, registered with another drop function
+        let other_health =
+            other.register_dynamic_component("Health", Layout::new::<u32>(), Some(drop_b));
+
+        let a = w.spawn();
+        unsafe { w.insert_by_id(a, health, &7u32 as *const u32 as *const u8) };
+        let moved = w.transfer(a, &mut other);
+        let value = other.get_by_id(moved, other_health).unwrap();
+        assert_eq!(unsafe { *value.cast::<u32>() }, 7);
+        assert_eq!(other.components.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "has another layout in the other world")]
+    fn transfer_dynamic_layout() {
+        let mut w = World::new();
+        let health = w.register_dynamic_component("Health", Layout::new::<u32>(), None);
+        let mut other = World::new();
+        other.register_dynamic_component("Health", Layout::new::<u64>(), None);
+        let a = w.spawn();
+        unsafe { w.insert_by_id(a, health, &7u32 as *const u32 as *const u8) };
+        w.transfer(a, &mut other);
+    }
+
+    #[test]
+    fn append_world() {
+        let mut background = World::new();
+        background.register_type::<Follows>().with_map_entities();
+        let root = background.spawn_with((0u32,));
+        let child = background.spawn_with((1u32, Follows(root)));
+        background.set_parent(child, root);
+        background.add_pair(root, Likes, child);
+
+        let mut w = crate::world::World::new_in(Counting::default());
+        let first = w.spawn_with((String::from("first"),));
+        let map = w.append(&mut background);
+        assert_eq!(map.len(), 2);
+        assert_eq!(background.query::<u32>().count(), 0);
+
+        let (root, child) = (map[&root], map[&child]);
+        assert_ne!(root, first);
+        assert_eq!(*w.get::<u32>(child).unwrap(), 1);
+        assert_eq!(w.parent(child), Some(root));
+        assert_eq!(w.children(root), [child]);
+        assert_eq!(w.get::<Follows>(child).unwrap().0, root);
+        assert!(w.has_pair::<Likes>(root, child));
+        let mut values = w.query::<u32>().copied().collect::<Vec<_>>();
+        values.sort();
+        assert_eq!(values, [0, 1]);
+    }
+}
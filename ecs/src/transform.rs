@@ -0,0 +1,319 @@
+use alloc::vec::Vec;
+use core::ops::Mul;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{
+    component::Component,
+    entity::Entity,
+    hierarchy::Parent,
+    world::{World, WorldAllocator},
+};
+
+/// Translation, rotation and uniform scale, applied in reverse order.
+///
+/// The rotation is a unit quaternion stored as `[x, y, z, w]`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transform {
+    pub translation: [f32; 3],
+    pub rotation: [f32; 4],
+    pub scale: f32,
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        translation: [0.0; 3],
+        rotation: [0.0, 0.0, 0.0, 1.0],
+        scale: 1.0,
+    };
+
+    pub fn from_translation(translation: [f32; 3]) -> Self {
+        Self {
+            translation,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn transform_point(&self, point: [f32; 3]) -> [f32; 3] {
+        let scaled = point.map(|x| x * self.scale);
+        let rotated = rotate(self.rotation, scaled);
+        return [0, 1, 2].map(|i| rotated[i] + self.translation[i]);
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        return Self::IDENTITY;
+    }
+}
+
+// `parent * child` places the child in the space of the parent
+impl Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, child: Transform) -> Transform {
+        let [x1, y1, z1, w1] = self.rotation;
+        let [x2, y2, z2, w2] = child.rotation;
+        return Transform {
+            translation: self.transform_point(child.translation),
+            rotation: [
+                w1 * x2 + x1 * w2 + y1 * z2 - z1 * y2,
+                w1 * y2 - x1 * z2 + y1 * w2 + z1 * x2,
+                w1 * z2 + x1 * y2 - y1 * x2 + z1 * w2,
+                w1 * w2 - x1 * x2 - y1 * y2 - z1 * z2,
+            ],
+            scale: self.scale * child.scale,
+        };
+    }
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    return [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ];
+}
+
+// v + 2w(q x v) + 2q x (q x v)
+fn rotate(rotation: [f32; 4], v: [f32; 3]) -> [f32; 3] {
+    let q = [rotation[0], rotation[1], rotation[2]];
+    let t = cross(q, v).map(|x| x * 2.0);
+    let u = cross(q, t);
+    return [0, 1, 2].map(|i| v[i] + rotation[3] * t[i] + u[i]);
+}
+
+/// The transform of an entity relative to its parent.
+///
+/// Changes made through `set` or `get_mut` are picked up by the next
+/// `propagate_transforms`, everything else is left alone.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LocalTransform {
+    transform: Transform,
+    changed: bool,
+}
+
+impl LocalTransform {
+    pub fn new(transform: Transform) -> Self {
+        Self {
+            transform,
+            changed: true,
+        }
+    }
+
+    pub fn get(&self) -> &Transform {
+        return &self.transform;
+    }
+
+    pub fn get_mut(&mut self) -> &mut Transform {
+        self.changed = true;
+        return &mut self.transform;
+    }
+
+    pub fn set(&mut self, transform: Transform) -> () {
+        *self.get_mut() = transform;
+    }
+
+    pub fn changed(&self) -> bool {
+        return self.changed;
+    }
+
+    pub(crate) fn mark_changed(&mut self) -> () {
+        self.changed = true;
+    }
+}
+
+impl Default for LocalTransform {
+    fn default() -> Self {
+        return Self::new(Transform::IDENTITY);
+    }
+}
+
+impl Component for LocalTransform {
+    fn name() -> &'static str {
+        return "LocalTransform";
+    }
+}
+
+/// The transform of an entity in world space, written by `propagate_transforms`.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct GlobalTransform(Transform);
+
+impl GlobalTransform {
+    pub fn get(&self) -> &Transform {
+        return &self.0;
+    }
+}
+
+impl Component for GlobalTransform {
+    fn name() -> &'static str {
+        return "GlobalTransform";
+    }
+}
+
+/// Updates the `GlobalTransform` of every entity with a `LocalTransform`.
+///
+/// Walks from the roots down to the leaves and only recomputes the subtrees
+/// below a local transform that changed since the last call. Entities get a
+/// `GlobalTransform` on their first pass. An entity whose parent has no
+/// `LocalTransform` is a root of its own, placed relative to the origin.
+pub fn propagate_transforms<A>(world: &mut World<A>) -> ()
+where
+    A: WorldAllocator,
+{
+    let with_global = world
+        .query::<(LocalTransform, GlobalTransform)>()
+        .with_entities()
+        .map(|(x, _)| x)
+        .collect::<FxHashSet<_>>();
+    let added = world
+        .query::<LocalTransform>()
+        .with_entities()
+        .map(|(x, _)| x)
+        .filter(|x| !with_global.contains(x))
+        .collect::<Vec<_>>();
+    world.add_batch(added.iter().map(|x| (*x, GlobalTransform::default())));
+    let added = added.into_iter().collect::<FxHashSet<_>>();
+
+    let mut children = FxHashMap::<Entity, Vec<Entity>>::default();
+    for (entity, (_, parent)) in world.query::<(LocalTransform, Parent)>().with_entities() {
+        let parent = parent.get();
+        if with_global.contains(&parent) || added.contains(&parent) {
+            children.entry(parent).or_default().push(entity);
+        }
+    }
+    let placed = children
+        .values()
+        .flatten()
+        .copied()
+        .collect::<FxHashSet<_>>();
+
+    let mut rows = world
+        .query_mut::<(LocalTransform, GlobalTransform)>()
+        .with_entities()
+        .collect::<FxHashMap<_, _>>();
+    let mut stack = rows
+        .keys()
+        .filter(|x| !placed.contains(x))
+        .map(|x| (*x, Transform::IDENTITY, false))
+        .collect::<Vec<(Entity, Transform, bool)>>();
+    while let Some((entity, parent_global, parent_changed)) = stack.pop() {
+        let (local, global) = rows.get_mut(&entity).unwrap();
+        let changed = parent_changed || local.changed || added.contains(&entity);
+        if changed {
+            global.0 = parent_global * local.transform;
+        }
+        local.changed = false;
+        for child in children.get(&entity).into_iter().flatten() {
+            stack.push((*child, global.0, changed));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{propagate_transforms, GlobalTransform, LocalTransform, Transform};
+    use std::alloc::Global;
+
+    type World = crate::world::World<Global>;
+
+    // a quarter turn around z
+    const TURN: [f32; 4] = [
+        0.0,
+        0.0,
+        core::f32::consts::FRAC_1_SQRT_2,
+        core::f32::consts::FRAC_1_SQRT_2,
+    ];
+
+    fn close(a: [f32; 3], b: [f32; 3]) -> bool {
+        return a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5);
+    }
+
+    fn global(w: &World, entity: crate::entity::Entity) -> [f32; 3] {
+        return w.get::<GlobalTransform>(entity).unwrap().get().translation;
+    }
+
+    #[test]
+    fn transform_compose() {
+        let parent = Transform {
+            translation: [1.0, 0.0, 0.0],
+            rotation: TURN,
+            scale: 2.0,
+        };
+        let child = Transform::from_translation([1.0, 0.0, 0.0]);
+        let world = parent * child;
+        assert!(close(world.translation, [1.0, 2.0, 0.0]));
+        assert_eq!(world.scale, 2.0);
+        assert!(close(
+            (world * Transform::from_translation([1.0, 0.0, 0.0])).translation,
+            [1.0, 4.0, 0.0]
+        ));
+    }
+
+    #[test]
+    fn transform_propagate() {
+        let mut w = World::new();
+        let root = w.spawn_with((LocalTransform::new(Transform::from_translation([
+            1.0, 0.0, 0.0,
+        ])),));
+        let child = w.spawn_with((LocalTransform::new(Transform::from_translation([
+            0.0, 1.0, 0.0,
+        ])),));
+        let leaf = w.spawn_with((LocalTransform::new(Transform::from_translation([
+            0.0, 0.0, 1.0,
+        ])),));
+        w.set_parent(child, root);
+        w.set_parent(leaf, child);
+
+        propagate_transforms(&mut w);
+        assert!(close(global(&w, root), [1.0, 0.0, 0.0]));
+        assert!(close(global(&w, leaf), [1.0, 1.0, 1.0]));
+        assert!(!w.get::<LocalTransform>(leaf).unwrap().changed());
+
+        // a global written by hand survives as long as nothing above it changes
+        w.get_mut::<GlobalTransform>(leaf).unwrap().0 = Transform::IDENTITY;
+        propagate_transforms(&mut w);
+        assert!(close(global(&w, leaf), [0.0; 3]));
+
+        w.get_mut::<LocalTransform>(child)
+            .unwrap()
+            .get_mut()
+            .translation = [0.0, 2.0, 0.0];
+        propagate_transforms(&mut w);
+        assert!(close(global(&w, root), [1.0, 0.0, 0.0]));
+        assert!(close(global(&w, leaf), [1.0, 2.0, 1.0]));
+
+        // moving a subtree recomputes it
+        w.remove_parent(child);
+        propagate_transforms(&mut w);
+        assert!(close(global(&w, leaf), [0.0, 2.0, 1.0]));
+    }
+
+    #[test]
+    fn transform_parent_without_local() {
+        let mut w = World::new();
+        let group = w.spawn_with((0u32,));
+        let child = w.spawn_with((LocalTransform::new(Transform::from_translation([
+            0.0, 1.0, 0.0,
+        ])),));
+        let leaf = w.spawn_with((LocalTransform::new(Transform::from_translation([
+            0.0, 0.0, 1.0,
+        ])),));
+        w.set_parent(child, group);
+        w.set_parent(leaf, child);
+
+        // the child starts a tree of its own
+        propagate_transforms(&mut w);
+        assert!(close(global(&w, child), [0.0, 1.0, 0.0]));
+        assert!(close(global(&w, leaf), [0.0, 1.0, 1.0]));
+        assert!(w.get::<GlobalTransform>(group).is_none());
+
+        w.get_mut::<LocalTransform>(child)
+            .unwrap()
+            .set(Transform::from_translation([2.0, 0.0, 0.0]));
+        propagate_transforms(&mut w);
+        assert!(!w.get::<LocalTransform>(child).unwrap().changed());
+        assert!(close(global(&w, leaf), [2.0, 0.0, 1.0]));
+    }
+}
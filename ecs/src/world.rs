@@ -1,48 +1,321 @@
-use alloc::vec::Vec;
-use core::alloc::Allocator;
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
+use core::{
+    alloc::{Allocator, Layout},
+    any::{type_name, TypeId},
+    mem, ptr,
+    ptr::NonNull,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
-use rustc_hash::FxHashSet;
+use rustc_hash::{FxHashMap, FxHashSet};
 
 use crate::{
-    archetype::ArchetypeSet,
+    archetype::{ArchetypeSet, TypeInfo},
     bundle::Bundle,
+    command::{Command, Commands},
+    component::{
+        Component, ComponentHook, ComponentHooks, ComponentId, ComponentInfo, ComponentSet,
+        StorageType,
+    },
     entity::Entity,
-    query::{Query, QueryMut},
+    hierarchy::Parent,
+    observer::{EventKey, Observers},
+    query::{DynamicQuery, Query, QueryMut, SparseColumns},
+    registry::{TypeRegistrar, TypeRegistration, TypeRegistry},
+    relation::Relations,
+    sparse::SparseSets,
 };
 
+/// Any allocator a `World` can be created with.
+///
+/// Lets code that is generic over worlds name the bound without enabling
+/// `allocator_api`.
+pub trait WorldAllocator: Allocator + Clone + 'static {}
+
+impl<A> WorldAllocator for A where A: Allocator + Clone + 'static {}
+
+/// Tells worlds apart, for data that only makes sense in the world it came from.
+#[derive(Eq, Hash, PartialEq, Clone, Copy, Debug)]
+pub struct WorldId(u64);
+
+impl WorldId {
+    fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        return Self(NEXT.fetch_add(1, Ordering::Relaxed));
+    }
+}
+
 pub struct World<A>
 where
     A: Allocator,
 {
-    archetypes: ArchetypeSet<A>,
-    entities: FxHashSet<Entity>,
-    next_entity: u64,
-    allocator: A,
+    pub(crate) archetypes: ArchetypeSet<A>,
+    pub(crate) sparse: SparseSets<A>,
+    pub(crate) relations: Relations,
+    pub(crate) entities: FxHashSet<Entity>,
+    //indexed by `ComponentId`
+    pub(crate) components: Vec<ComponentInfo<A>>,
+    component_ids: FxHashMap<TypeId, ComponentId>,
+    types: TypeRegistry,
+    //set once a component has hooks or lifecycle observers, so worlds without
+    //any skip looking for them
+    pub(crate) hooked: bool,
+    pub(crate) deferred: VecDeque<Command<A>>,
+    pub(crate) observers: Observers<A>,
+    pub(crate) next_entity: u64,
+    pub(crate) id: WorldId,
+    pub(crate) allocator: A,
 }
 
 impl<A> World<A>
 where
-    A: Allocator + Clone,
+    A: WorldAllocator,
 {
     #[cfg(feature = "std")]
     pub fn new() -> World<std::alloc::Global> {
         World {
             archetypes: ArchetypeSet::new(),
+            sparse: SparseSets::new(),
+            relations: Relations::default(),
             entities: FxHashSet::default(),
+            components: Vec::new(),
+            component_ids: FxHashMap::default(),
+            types: TypeRegistry::new(),
+            hooked: false,
+            deferred: VecDeque::new(),
+            observers: Observers::new(),
             allocator: std::alloc::Global,
             next_entity: 0,
+            id: WorldId::next(),
         }
     }
 
     pub fn new_in(allocator: A) -> Self {
         Self {
             archetypes: ArchetypeSet::new(),
+            sparse: SparseSets::new(),
+            relations: Relations::default(),
             entities: FxHashSet::default(),
+            components: Vec::new(),
+            component_ids: FxHashMap::default(),
+            types: TypeRegistry::new(),
+            hooked: false,
+            deferred: VecDeque::new(),
+            observers: Observers::new(),
             allocator,
             next_entity: 0,
+            id: WorldId::next(),
+        }
+    }
+
+    pub fn id(&self) -> WorldId {
+        return self.id;
+    }
+
+    /// Registers the metadata of `T`.
+    ///
+    /// Archetypes created afterwards store `T` with the registered `TypeInfo`,
+    /// registering again replaces the previous metadata.
+    pub fn register_component<T>(&mut self) -> ComponentId
+    where
+        T: Component,
+    {
+        let info = ComponentInfo::new::<T>();
+        let type_info = info.type_info;
+        self.hooked |= !info.hooks.is_empty();
+        let Some(id) = self.component_ids.get(&TypeId::of::<T>()).copied() else {
+            return self.push_component(info);
+        };
+        self.components[id.index()] = info;
+        for archetype in self.archetypes.iter_mut() {
+            if let Some(x) = archetype.type_info_of_mut(&id) {
+                *x = type_info;
+            }
+        }
+        if let Some(set) = self.sparse.get_mut(id) {
+            *set.type_info_mut() = type_info;
+        }
+        return id;
+    }
+
+    /// The hooks the world runs for `T`, for setting them up at runtime.
+    pub fn component_hooks_mut<T>(&mut self) -> &mut ComponentHooks<A>
+    where
+        T: Component,
+    {
+        let id = self.init_component(ComponentInfo::new::<T>());
+        self.hooked = true;
+        return &mut self.components[id.index()].hooks;
+    }
+
+    /// Registers a component that has no rust type, like the ones defined by scripts.
+    ///
+    /// Its values are handled as raw bytes of `layout`, `drop` runs whenever one
+    /// gets removed.
+    pub fn register_dynamic_component(
+        &mut self,
+        name: &'static str,
+        layout: Layout,
+        drop: Option<unsafe fn(*mut u8) -> ()>,
+    ) -> ComponentId {
+        return self.push_component(ComponentInfo::dynamic(TypeInfo::dynamic(
+            name, layout, drop,
+        )));
+    }
+
+    pub(crate) fn push_component(&mut self, info: ComponentInfo<A>) -> ComponentId {
+        let id = ComponentId(self.components.len() as u32);
+        if let Some(type_id) = info.type_info.type_id() {
+            self.component_ids.insert(type_id, id);
+        }
+        self.components.push(info);
+        return id;
+    }
+
+    //the id of a rust type, handing out a new one with `info` the first time it shows up
+    pub(crate) fn init_component(&mut self, info: ComponentInfo<A>) -> ComponentId {
+        let type_id = info
+            .type_info
+            .type_id()
+            .expect("dynamic components need to be registered");
+        if let Some(id) = self.component_ids.get(&type_id) {
+            return *id;
+        }
+        self.hooked |= !info.hooks.is_empty();
+        return self.push_component(info);
+    }
+
+    pub fn component_id<T>(&self) -> Option<ComponentId>
+    where
+        T: Component,
+    {
+        return self.component_ids.get(&TypeId::of::<T>()).copied();
+    }
+
+    pub fn component_info(&self, type_id: TypeId) -> Option<&ComponentInfo<A>> {
+        return self.component_info_by_id(*self.component_ids.get(&type_id)?);
+    }
+
+    pub fn component_info_by_id(&self, id: ComponentId) -> Option<&ComponentInfo<A>> {
+        return self.components.get(id.index());
+    }
+
+    /// Registers `T` in the type registry under its component name.
+    pub fn register_type<T>(&mut self) -> TypeRegistrar<'_, T>
+    where
+        T: Component,
+    {
+        return self.types.register::<T>();
+    }
+
+    pub fn type_registry(&self) -> &TypeRegistry {
+        return &self.types;
+    }
+
+    pub fn type_registry_mut(&mut self) -> &mut TypeRegistry {
+        return &mut self.types;
+    }
+
+    pub fn type_registration(&self, type_id: TypeId) -> Option<&TypeRegistration> {
+        return self.types.get(type_id);
+    }
+
+    pub fn type_registration_by_name(&self, name: &str) -> Option<&TypeRegistration> {
+        return self.types.get_by_name(name);
+    }
+
+    //the ids of a bundle in declaration order, registering the missing ones
+    pub(crate) fn init_bundle<'b, T>(&mut self) -> Vec<ComponentId>
+    where
+        T: Bundle<'b>,
+    {
+        let ids = T::component_info()
+            .into_iter()
+            .map(|x| self.init_component(x))
+            .collect::<Vec<_>>();
+        self.assert_unique::<T>(&ids);
+        return ids;
+    }
+
+    //a component showing up twice in `T` would corrupt an archetype or hand out
+    //two mutable references to it, checked on ids that were looked up anyway
+    fn assert_unique<'b, T>(&self, ids: &[ComponentId]) -> ()
+    where
+        T: Bundle<'b>,
+    {
+        if T::COUNT < 2 {
+            return;
+        }
+        for (i, id) in ids.iter().enumerate() {
+            if ids[..i].contains(id) {
+                panic!(
+                    "bundle `{}` contains the component `{}` more than once",
+                    type_name::<T>(),
+                    self.components[id.index()].type_info.name()
+                );
+            }
         }
     }
 
+    //none if one of the components never showed up, nothing can have it then
+    fn bundle_ids<'b, T>(&self) -> Option<Vec<ComponentId>>
+    where
+        T: Bundle<'b>,
+    {
+        return T::type_ids()
+            .iter()
+            .map(|x| self.component_ids.get(x).copied())
+            .collect();
+    }
+
+    pub(crate) fn is_sparse(&self, id: ComponentId) -> bool {
+        return self.components[id.index()].type_info.storage() == StorageType::Sparse;
+    }
+
+    //splits ids into the ones stored in sparse sets and the ones in archetype tables
+    fn split_storage(&self, ids: &[ComponentId]) -> (Vec<ComponentId>, Vec<ComponentId>) {
+        return ids.iter().partition(|x| self.is_sparse(**x));
+    }
+
+    //pointers to the components `ids` of `entity`, no matter where they are stored
+    fn fetch(&self, entity: Entity, ids: &[ComponentId]) -> Option<Box<[*mut u8]>> {
+        let archetype = self.archetypes.get_by_entity(entity);
+        let row = archetype.and_then(|x| x.index_of(entity));
+        return ids
+            .iter()
+            .map(|id| {
+                if self.is_sparse(*id) {
+                    return self.sparse.get(*id)?.get(entity);
+                }
+                return Some(unsafe { archetype?.get_by_index(row?, &[*id])?[0] });
+            })
+            .collect();
+    }
+
+    fn has_ids(&self, entity: Entity, ids: &[ComponentId]) -> bool {
+        let archetype = self.archetypes.get_by_entity(entity);
+        return ids.iter().all(|id| {
+            if self.is_sparse(*id) {
+                return self.sparse.get(*id).is_some_and(|x| x.contains(entity));
+            }
+            return archetype.is_some_and(|x| x.contains(id));
+        });
+    }
+
+    //archetypes are keyed by their sorted ids so the order of a bundle doesn't matter
+    pub(crate) fn sorted_components(
+        &self,
+        ids: &[ComponentId],
+    ) -> (Vec<ComponentId>, Vec<TypeInfo>) {
+        let mut ids = ids.to_vec();
+        ids.sort();
+        let type_info = ids
+            .iter()
+            .map(|x| self.components[x.index()].type_info)
+            .collect();
+        return (ids, type_info);
+    }
+
     pub fn spawn(&mut self) -> Entity {
         let entity = Entity(self.next_entity);
         self.entities.insert(entity);
@@ -51,106 +324,427 @@ where
     }
 
     pub fn destroy(&mut self, entity: Entity) -> () {
+        self.run_remove_hooks(entity);
+        let batch = FxHashSet::from_iter([entity]);
+        self.detach_batch(&batch);
+        self.remove_targets(&batch);
         self.entities.remove(&entity);
-        self.archetypes.iter_mut().for_each(|x| x.destroy(entity));
+        self.archetypes.destroy(entity);
+        self.sparse.remove_entity(entity);
+        self.flush();
+    }
+
+    pub fn despawn_batch(&mut self, entities: &[Entity]) -> () {
+        let batch = entities
+            .iter()
+            .copied()
+            .filter(|x| self.entities.remove(x))
+            .collect::<FxHashSet<_>>();
+        if batch.is_empty() {
+            return;
+        }
+        batch.iter().for_each(|x| self.run_remove_hooks(*x));
+        self.detach_batch(&batch);
+        self.remove_targets(&batch);
+        for set in self.sparse.iter_mut() {
+            batch.iter().for_each(|x| {
+                set.remove(*x);
+            });
+        }
+
+        self.archetypes.retain(|archetype| {
+            let matching = archetype
+                .entity_iter()
+                .filter(|x| batch.contains(x))
+                .count();
+            if matching == 0 {
+                return true;
+            }
+            // every entity goes so release the whole archetype at once
+            if matching == archetype.len() {
+                return false;
+            }
+            // walking backwards keeps the rows that get swapped in already visited
+            for i in (0..archetype.len()).rev() {
+                if batch.contains(&archetype.entities[i]) {
+                    archetype.destroy_index(i);
+                }
+            }
+            return true;
+        });
+        self.flush();
     }
 
-    pub fn add<'b, T>(&mut self, entity: Entity, mut data: T) -> ()
+    /// Despawns every entity with all of `T`, the empty bundle despawns nothing.
+    pub fn despawn_matching<'b, T>(&mut self) -> ()
     where
         T: Bundle<'b>,
     {
-        //unoptimal but it works
-        let type_ids = T::type_ids();
-        let type_infos = T::type_info();
-        let mut new_data = unsafe { data.as_ptrs() };
-
-        let archetype = match self.archetypes.get_mut(&type_ids) {
-            Some(a) => a,
-            None => {
-                let archetype = self.archetypes.get_by_entity_mut(entity);
-                if archetype.is_none() {
-                    if !self.archetypes.has(&type_ids) {
-                        self.archetypes
-                            .add(&type_ids, &type_infos, self.allocator.clone());
-
-                        let archetype = self.archetypes.get_mut(&type_ids).unwrap();
-
-                        unsafe { archetype.add(entity, &new_data) };
-                    }
-                    return;
-                }
-                let archetype = archetype.unwrap();
+        let Some(ids) = self.bundle_ids::<T>() else {
+            return;
+        };
+        // every archetype would match, that is what `clear` is for
+        if ids.is_empty() {
+            return;
+        }
+        let (sparse, table) = self.split_storage(&ids);
+        let mask = ComponentSet::from_ids(&table);
+        // only some entities of an archetype might match, or the survivors
+        // have to be detached from the despawned ones
+        let linked = self.component_id::<Parent>().is_some() || !self.relations.is_empty();
+        let linked = linked || self.hooked;
+        if !sparse.is_empty() || linked {
+            let matching = self
+                .archetypes
+                .get_similiar(&table)
+                .unwrap_or_default()
+                .iter()
+                .flat_map(|x| x.entity_iter())
+                .filter(|x| self.has_ids(**x, &sparse))
+                .copied()
+                .collect::<Vec<_>>();
+            self.despawn_batch(&matching);
+            return;
+        }
 
-                let old_data = unsafe {
-                    let Some(old_data) = archetype.remove_whole(entity) else {
-                        return;
-                    };
-                    old_data
-                };
+        let mut despawned = Vec::new();
+        self.archetypes.retain(|archetype| {
+            if !archetype.contains_all(&mask) {
+                return true;
+            }
+            despawned.extend(archetype.entity_iter().copied());
+            return false;
+        });
+        for entity in despawned {
+            self.entities.remove(&entity);
+            self.sparse.remove_entity(entity);
+        }
+    }
+
+    //despawns every entity, the archetypes stay around so they can be refilled
+    pub fn clear(&mut self) -> () {
+        self.run_all_remove_hooks();
+        self.entities.clear();
+        self.archetypes.clear();
+        self.sparse.iter_mut().for_each(|x| x.clear());
+        self.relations = Relations::default();
+        self.flush();
+    }
 
-                new_data = [old_data, unsafe { data.as_ptrs() }]
-                    .concat()
-                    .into_boxed_slice();
-                let new_type_ids = [archetype.type_ids.as_ref(), type_ids.as_ref()].concat();
-                let new_type_infos = [
-                    archetype
-                        .types
-                        .clone()
-                        .into_values()
-                        .collect::<Vec<_>>()
-                        .as_slice(),
-                    type_infos.as_ref(),
-                ]
-                .concat();
-
-                self.archetypes
-                    .add(&new_type_ids, &new_type_infos, self.allocator.clone());
-                let new_archetype = self.archetypes.get_mut(&new_type_ids).unwrap();
-                new_archetype
+    //releases empty archetypes and column capacity that isn't used
+    pub fn compact(&mut self) -> () {
+        self.archetypes.retain(|archetype| {
+            if archetype.empty() {
+                return false;
             }
-        };
+            archetype.shrink_to_fit();
+            return true;
+        });
+    }
 
-        unsafe {
-            //archetype.add(entity, &data.as_ptrs());
-            archetype.add(entity, &new_data);
-        };
+    pub fn spawn_with<'b, T>(&mut self, data: T) -> Entity
+    where
+        T: Bundle<'b>,
+    {
+        let entity = self.spawn();
+        self.entity_mut(entity).insert(data);
+        return entity;
     }
 
-    pub fn remove<'b, T>(&mut self, entity: Entity) -> Option<()>
+    /// Spawns an entity for every bundle `iter` yields.
+    ///
+    /// The table is reserved once for all of them and every value is moved
+    /// straight into its column. Hooks and observers run once every entity is
+    /// in place.
+    pub fn spawn_batch<'b, T, I>(&mut self, iter: I) -> impl Iterator<Item = Entity>
     where
         T: Bundle<'b>,
+        I: IntoIterator<Item = T>,
     {
-        let type_ids = T::type_ids();
-        let rest;
-
-        let archetype = self.archetypes.get_by_entity_mut(entity)?;
-        rest = unsafe { archetype.remove(entity, &type_ids)? };
-        let new_type_ids = archetype.type_ids.clone();
-        let new_type_info = archetype
-            .type_ids
+        let bundle = self.init_bundle::<T>();
+        // collected first so the size is known up front
+        let batch = iter.into_iter().collect::<Vec<_>>();
+        let entities = (0..batch.len()).map(|_| self.spawn()).collect::<Vec<_>>();
+        let (_, table) = self.split_storage(&bundle);
+        let (ids, type_info) = self.sorted_components(&table);
+        // entities with only sparse components still get a row, like in `move_components`
+        let columns = unsafe {
+            self.archetypes
+                .push_uninit(&ids, &type_info, self.allocator.clone(), &entities)
+        };
+
+        // where each component of the bundle goes, in declaration order
+        let dst = bundle
             .iter()
-            .map(|x| archetype.types[x])
+            .map(|x| {
+                let size = self.components[x.index()].type_info.layout().size();
+                return ids.binary_search(x).ok().map(|i| (columns[i], size));
+            })
             .collect::<Vec<_>>();
+        for (row, (mut data, entity)) in batch.into_iter().zip(entities.iter()).enumerate() {
+            let ptrs = unsafe { data.as_ptrs() };
+            for ((id, dst), src) in bundle.iter().zip(dst.iter()).zip(ptrs.iter()) {
+                let Some((column, size)) = dst else {
+                    let type_info = self.components[id.index()].type_info;
+                    let set = self
+                        .sparse
+                        .get_or_add(*id, type_info, self.allocator.clone());
+                    unsafe { set.insert(*entity, *src) };
+                    continue;
+                };
+                unsafe { ptr::copy_nonoverlapping(*src, column.add(row * size), *size) };
+            }
+            mem::forget(data);
+        }
+
+        if self.hooked {
+            for entity in entities.iter() {
+                self.run_hooks(*entity, &bundle, |x| x.on_add, EventKey::Add);
+                self.run_hooks(*entity, &bundle, |x| x.on_insert, EventKey::Insert);
+            }
+            self.flush();
+        }
+        return entities.into_iter();
+    }
+
+    //makes room for `additional` more entities made up of exactly the bundle `T`
+    pub fn reserve<'b, T>(&mut self, additional: usize) -> ()
+    where
+        T: Bundle<'b>,
+    {
+        let bundle = self.init_bundle::<T>();
+        let (_, table) = self.split_storage(&bundle);
+        let (ids, type_info) = self.sorted_components(&table);
+        let archetype = self
+            .archetypes
+            .get_or_add(&ids, &type_info, self.allocator.clone());
+        archetype.reserve(additional);
+    }
+
+    pub fn entity_mut(&mut self, entity: Entity) -> EntityMut<'_, A> {
+        return EntityMut::new(self, entity);
+    }
+
+    pub fn add<'b, T>(&mut self, entity: Entity, data: T) -> ()
+    where
+        T: Bundle<'b>,
+    {
+        self.entity_mut(entity).insert(data);
+    }
 
-        if !self.archetypes.has(&new_type_ids) {
+    /// Adds the bundle to every entity `iter` yields, like `add` for each of them.
+    ///
+    /// Every table the entities end up in is reserved once up front and the
+    /// values are moved straight from the bundles. Deferred changes of the
+    /// hooks and observers are applied once every entity is done.
+    pub fn add_batch<'b, T, I>(&mut self, iter: I) -> ()
+    where
+        T: Bundle<'b>,
+        I: IntoIterator<Item = (Entity, T)>,
+    {
+        let bundle = self.init_bundle::<T>();
+        let batch = iter.into_iter().collect::<Vec<_>>();
+        let (_, table) = self.split_storage(&bundle);
+        let mut targets = FxHashMap::<Vec<ComponentId>, usize>::default();
+        for (entity, _) in batch.iter() {
+            let mut ids = self
+                .archetypes
+                .get_by_entity(*entity)
+                .map_or(Vec::new(), |x| x.component_ids.to_vec());
+            ids.extend(
+                table
+                    .iter()
+                    .filter(|x| !ids.contains(x))
+                    .collect::<Vec<_>>(),
+            );
+            *targets.entry(ids).or_default() += 1;
+        }
+        for (ids, additional) in targets {
+            let (ids, type_info) = self.sorted_components(&ids);
             self.archetypes
-                .add(&new_type_ids, &new_type_info, self.allocator.clone());
+                .get_or_add(&ids, &type_info, self.allocator.clone())
+                .reserve(additional);
         }
 
-        let archetype = self.archetypes.get_mut(&type_ids).unwrap();
-        //whatever the hell this is
-        unsafe {
-            archetype.add(entity, &rest);
-        };
+        for (entity, mut data) in batch {
+            let ptrs = unsafe { data.as_ptrs() };
+            let added = bundle
+                .iter()
+                .copied()
+                .zip(ptrs.iter().copied())
+                .collect::<Vec<_>>();
+            unsafe { self.relocate(entity, &added, &[]) };
+            mem::forget(data);
+        }
+        self.flush();
+    }
+
+    pub fn remove<'b, T>(&mut self, entity: Entity) -> Option<()>
+    where
+        T: Bundle<'b>,
+    {
+        let ids = self.bundle_ids::<T>()?;
+        if !self.has_ids(entity, &ids) {
+            return None;
+        }
+        unsafe { self.relocate(entity, &[], &ids) };
+        self.flush();
+        return Some(());
+    }
+
+    /// Moves the value behind `data` into the component `id` of `entity`.
+    ///
+    /// # Safety
+    /// `data` has to point at a valid value of the component, it is owned by
+    /// the world afterwards and must not be dropped by the caller.
+    pub unsafe fn insert_by_id(&mut self, entity: Entity, id: ComponentId, data: *const u8) -> () {
+        self.entity_mut(entity).insert_by_id(id, data);
+    }
+
+    pub fn remove_by_id(&mut self, entity: Entity, id: ComponentId) -> Option<()> {
+        if !self.has_ids(entity, &[id]) {
+            return None;
+        }
+        unsafe { self.relocate(entity, &[], &[id]) };
+        self.flush();
         return Some(());
     }
 
+    pub fn get_by_id(&self, entity: Entity, id: ComponentId) -> Option<*const u8> {
+        return Some(self.fetch(entity, &[id])?[0]);
+    }
+
+    pub fn get_mut_by_id(&mut self, entity: Entity, id: ComponentId) -> Option<*mut u8> {
+        return Some(self.fetch(entity, &[id])?[0]);
+    }
+
+    /// Iterates over every entity that has all of `ids`, handing out pointers
+    /// to its components in the order of `ids`.
+    pub fn query_by_ids(&mut self, ids: &[ComponentId]) -> DynamicQuery<'_, A> {
+        let Some((table, sparse)) = sparse_columns(&self.components, &self.sparse, ids) else {
+            return DynamicQuery::new(Default::default(), ids, Default::default());
+        };
+        let archetypes = self.archetypes.get_similiar(&table).unwrap_or_default();
+        return DynamicQuery::new(archetypes, ids, sparse);
+    }
+
+    /// Moves `entity` into the archetype made up of its current components
+    /// without `removed` and with `added`, copying every component once.
+    ///
+    /// Components in `removed` or replaced by `added` get dropped, the values
+    /// behind `added` are moved in and must not be used afterwards.
+    pub(crate) unsafe fn relocate(
+        &mut self,
+        entity: Entity,
+        added: &[(ComponentId, *mut u8)],
+        removed: &[ComponentId],
+    ) {
+        if !removed.is_empty() {
+            self.unlink(entity, removed);
+        }
+        if !self.hooked {
+            self.move_components(entity, added, removed);
+            return;
+        }
+        let had = self.ids_of(entity);
+        let removed_ids = removed
+            .iter()
+            .filter(|x| had.contains(x))
+            .copied()
+            .collect::<Vec<_>>();
+        let added_ids = added.iter().map(|(x, _)| *x).collect::<Vec<_>>();
+        let new_ids = added_ids
+            .iter()
+            .filter(|x| !had.contains(x))
+            .copied()
+            .collect::<Vec<_>>();
+
+        self.run_hooks(entity, &removed_ids, |x| x.on_remove, EventKey::Remove);
+        self.move_components(entity, added, removed);
+        self.run_hooks(entity, &new_ids, |x| x.on_add, EventKey::Add);
+        self.run_hooks(entity, &added_ids, |x| x.on_insert, EventKey::Insert);
+    }
+
+    unsafe fn move_components(
+        &mut self,
+        entity: Entity,
+        added: &[(ComponentId, *mut u8)],
+        removed: &[ComponentId],
+    ) {
+        // sparse components never move the entity to another archetype
+        let (sparse_added, added): (Vec<_>, Vec<_>) =
+            added.iter().partition(|(x, _)| self.is_sparse(*x));
+        let (sparse_removed, removed) = self.split_storage(removed);
+        for (id, data) in sparse_added {
+            let type_info = self.components[id.index()].type_info;
+            self.sparse
+                .get_or_add(id, type_info, self.allocator.clone())
+                .insert(entity, data);
+        }
+        for id in sparse_removed {
+            if let Some(set) = self.sparse.get_mut(id) {
+                set.remove(entity);
+            }
+        }
+
+        let mut components: Vec<(ComponentId, *mut u8)> = Vec::new();
+        let mut kept = Vec::new();
+
+        if let Some(archetype) = self.archetypes.get_by_entity_mut(entity) {
+            let same_types = added.iter().all(|(x, _)| archetype.contains(x))
+                && !removed.iter().any(|x| archetype.contains(x));
+            if same_types {
+                // nothing structural changes, overwrite the values in place
+                for (id, data) in added.iter() {
+                    let ty = *archetype.type_info_of(id).unwrap();
+                    let old = archetype.get(entity, &[*id]).unwrap()[0];
+                    ty.drop(old);
+                    ptr::copy_nonoverlapping(*data, old, ty.layout().size());
+                }
+                return;
+            }
+
+            let dropped = archetype
+                .component_ids
+                .iter()
+                .filter(|x| removed.contains(x) || added.iter().any(|(y, _)| y == *x))
+                .copied()
+                .collect::<Vec<_>>();
+            let kept_ids = archetype
+                .component_ids
+                .iter()
+                .filter(|x| !dropped.contains(x))
+                .copied()
+                .collect::<Vec<_>>();
+            let old_data = archetype.remove(entity, &dropped).unwrap();
+            kept = kept_ids.into_iter().zip(old_data).collect::<Vec<_>>();
+            components.extend_from_slice(&kept);
+        }
+        components.extend_from_slice(&added);
+
+        // entities without table components still get a row, otherwise queries
+        // over sparse components alone would never see them
+        components.sort_by_key(|(x, _)| *x);
+        let ids = components.iter().map(|(x, _)| *x).collect::<Vec<_>>();
+        let (_, type_info) = self.sorted_components(&ids);
+        let data = components.iter().map(|(_, x)| *x).collect::<Vec<_>>();
+
+        self.archetypes
+            .add_entity(&ids, &type_info, self.allocator.clone(), entity, &data);
+
+        for (id, data) in kept {
+            let layout = self.components[id.index()].type_info.layout();
+            self.allocator
+                .deallocate(NonNull::new(data).unwrap(), layout);
+        }
+    }
+
     pub fn get<'b, T>(&self, entity: Entity) -> Option<T::Target>
     where
         T: 'static + Bundle<'b>,
     {
-        let archetype = self.archetypes.get_by_entity(entity)?;
-        let data = unsafe { archetype.get(entity, &T::type_ids())? };
+        let ids = self.bundle_ids::<T>()?;
+        let data = self.fetch(entity, &ids)?;
         return Some(unsafe { T::from_ptr(&data) });
         //return Some(unsafe { &*(t[0] as *mut T) });
     }
@@ -159,8 +753,10 @@ where
     where
         T: 'static + Bundle<'b>,
     {
-        let archetype = self.archetypes.get_by_entity(entity)?;
-        let data = unsafe { archetype.get(entity, &T::type_ids())? };
+        let ids = self.bundle_ids::<T>()?;
+        // handing out two mutable references to the same component isn't allowed
+        self.assert_unique::<T>(&ids);
+        let data = self.fetch(entity, &ids)?;
         return Some(unsafe { T::from_ptr_mut(&data) });
     }
 
@@ -168,43 +764,342 @@ where
     where
         T: Bundle<'b>,
     {
-        if let Some(archetype) = self.archetypes.get(&T::type_ids()) {
-            return archetype.has(entity);
-        }
-        return false;
+        let Some(ids) = self.bundle_ids::<T>() else {
+            return false;
+        };
+        return self.has_ids(entity, &ids);
     }
 
+    /// Every entity with all of `T`.
+    ///
+    /// Archetypes are visited in the order they were created in and entities in
+    /// the order they were added to them, so worlds that went through the same
+    /// operations iterate in the same order.
     pub fn query<'world, 'query, T>(&'world self) -> Query<'query, T, A>
     where
         T: Bundle<'query>,
         'world: 'query,
     {
-        let archetype = self
-            .archetypes
-            .get_similiar(&T::type_ids())
-            .unwrap_or_default();
-        return Query::new(archetype);
+        let empty = || Query::new(Default::default(), Default::default(), Default::default());
+        let Some(ids) = self.bundle_ids::<T>() else {
+            return empty();
+        };
+        let Some((table, sparse)) = sparse_columns(&self.components, &self.sparse, &ids) else {
+            return empty();
+        };
+        let archetype = self.archetypes.get_similiar(&table).unwrap_or_default();
+        return Query::new(archetype, ids.into_boxed_slice(), sparse);
     }
 
+    //same order as `query`
     pub fn query_mut<'world, 'query, T>(&'world mut self) -> QueryMut<'query, T, A>
     where
         T: Bundle<'query>,
         'world: 'query,
     {
+        let empty = || QueryMut::new(Default::default(), Default::default(), Default::default());
+        let Some(ids) = self.bundle_ids::<T>() else {
+            return empty();
+        };
+        self.assert_unique::<T>(&ids);
+        let Some((table, sparse)) = sparse_columns(&self.components, &self.sparse, &ids) else {
+            return empty();
+        };
         //Don't unwrap
-        let archetype = self
+        let archetype = self.archetypes.get_similiar_mut(&table).unwrap_or_default();
+        return QueryMut::new(archetype, ids.into_boxed_slice(), sparse);
+    }
+}
+
+//the sparse set of every sparse component in `ids` and the table components that
+//archetypes have to match, none if a sparse component was never stored
+fn sparse_columns<'w, A>(
+    components: &[ComponentInfo<A>],
+    sparse: &'w SparseSets<A>,
+    ids: &[ComponentId],
+) -> Option<(Vec<ComponentId>, SparseColumns<'w, A>)>
+where
+    A: Allocator,
+{
+    let mut table = Vec::new();
+    let mut columns = Vec::with_capacity(ids.len());
+    for id in ids {
+        if components[id.index()].type_info.storage() == StorageType::Sparse {
+            columns.push(Some(sparse.get(*id)?));
+        } else {
+            table.push(*id);
+            columns.push(None);
+        }
+    }
+    return Some((table, columns.into_boxed_slice()));
+}
+
+// running hooks only needs the allocator bound of the struct, so dropping a
+// world can run them as well
+impl<A> World<A>
+where
+    A: Allocator,
+{
+    //every component `entity` has, in archetype tables and sparse sets
+    pub(crate) fn ids_of(&self, entity: Entity) -> Vec<ComponentId> {
+        let mut ids = self
             .archetypes
-            .get_similiar_mut(&T::type_ids())
-            .unwrap_or_default();
-        return QueryMut::new(archetype);
+            .get_by_entity(entity)
+            .map_or(Vec::new(), |x| x.component_ids.to_vec());
+        ids.extend(self.sparse.ids_of(entity));
+        return ids;
+    }
+
+    //runs the hooks of `ids` followed by the observers of the same event
+    fn run_hooks(
+        &mut self,
+        entity: Entity,
+        ids: &[ComponentId],
+        hook: fn(&ComponentHooks<A>) -> Option<ComponentHook<A>>,
+        key: fn(ComponentId) -> EventKey,
+    ) -> () {
+        for id in ids {
+            if let Some(hook) = hook(&self.components[id.index()].hooks) {
+                hook(&mut DeferredWorld::new(self), entity);
+            }
+            self.run_lifecycle_observers(key(*id), entity);
+        }
+    }
+
+    fn run_remove_hooks(&mut self, entity: Entity) -> () {
+        if self.hooked {
+            let ids = self.ids_of(entity);
+            self.run_hooks(entity, &ids, |x| x.on_remove, EventKey::Remove);
+        }
+    }
+
+    fn run_all_remove_hooks(&mut self) -> () {
+        if !self.hooked {
+            return;
+        }
+        let entities = self.entities.iter().copied().collect::<Vec<_>>();
+        entities.into_iter().for_each(|x| self.run_remove_hooks(x));
+    }
+
+    //applies the changes hooks deferred, including the ones those defer in turn
+    pub(crate) fn flush(&mut self) -> () {
+        while let Some(deferred) = self.deferred.pop_front() {
+            deferred(self);
+        }
+    }
+}
+
+impl<A> Drop for World<A>
+where
+    A: Allocator,
+{
+    fn drop(&mut self) {
+        // the components go along with the archetypes, so this is the last
+        // chance for their hooks to see them
+        self.run_all_remove_hooks();
+        self.deferred.clear();
+    }
+}
+
+/// The world as component hooks and observers see it.
+///
+/// Components can be read and changed in place, but anything that would move
+/// entities between archetypes has to go through `commands`. Those changes are
+/// applied once the operation that ran the hook is done.
+pub struct DeferredWorld<'w, A>
+where
+    A: Allocator,
+{
+    world: &'w mut World<A>,
+}
+
+impl<'w, A> DeferredWorld<'w, A>
+where
+    A: Allocator,
+{
+    pub(crate) fn new(world: &'w mut World<A>) -> Self {
+        Self { world }
+    }
+
+    pub fn world(&self) -> &World<A> {
+        return self.world;
+    }
+}
+
+impl<'w, A> DeferredWorld<'w, A>
+where
+    A: WorldAllocator,
+{
+    pub fn commands(&mut self) -> Commands<'_, A> {
+        return Commands::new(self.world);
+    }
+
+    pub fn get<'b, T>(&self, entity: Entity) -> Option<T::Target>
+    where
+        T: 'static + Bundle<'b>,
+    {
+        return self.world.get::<T>(entity);
+    }
+
+    pub fn get_mut<'b, T>(&mut self, entity: Entity) -> Option<T::TargetMut>
+    where
+        T: 'static + Bundle<'b>,
+    {
+        return self.world.get_mut::<T>(entity);
+    }
+
+    pub fn has<'b, T>(&self, entity: Entity) -> bool
+    where
+        T: Bundle<'b>,
+    {
+        return self.world.has::<T>(entity);
+    }
+
+    pub fn get_mut_by_id(&mut self, entity: Entity, id: ComponentId) -> Option<*mut u8> {
+        return self.world.get_mut_by_id(entity, id);
+    }
+
+    pub fn query<'a, 'query, T>(&'a self) -> Query<'query, T, A>
+    where
+        T: Bundle<'query>,
+        'a: 'query,
+    {
+        return self.world.query::<T>();
+    }
+
+    pub fn query_mut<'a, 'query, T>(&'a mut self) -> QueryMut<'query, T, A>
+    where
+        T: Bundle<'query>,
+        'a: 'query,
+    {
+        return self.world.query_mut::<T>();
+    }
+}
+
+/// Batches structural changes to a single entity.
+///
+/// Inserted components are buffered and every change is applied with a single
+/// archetype move once the `EntityMut` is dropped.
+pub struct EntityMut<'w, A>
+where
+    A: WorldAllocator,
+{
+    world: &'w mut World<A>,
+    entity: Entity,
+    added: Vec<(ComponentId, *mut u8)>,
+    removed: Vec<ComponentId>,
+}
+
+impl<'w, A> EntityMut<'w, A>
+where
+    A: WorldAllocator,
+{
+    fn new(world: &'w mut World<A>, entity: Entity) -> Self {
+        Self {
+            world,
+            entity,
+            added: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+
+    pub fn id(&self) -> Entity {
+        return self.entity;
+    }
+
+    pub fn insert<'b, T>(&mut self, mut data: T) -> &mut Self
+    where
+        T: Bundle<'b>,
+    {
+        let ids = self.world.init_bundle::<T>();
+        let ptrs = unsafe { data.as_ptrs() };
+        for (id, src) in ids.iter().zip(ptrs.iter()) {
+            unsafe { self.insert_by_id(*id, *src) };
+        }
+        // the values were moved into the buffer
+        mem::forget(data);
+        return self;
+    }
+
+    /// Buffers the value behind `data` as the component `id`.
+    ///
+    /// # Safety
+    /// Same as `World::insert_by_id`.
+    pub unsafe fn insert_by_id(&mut self, id: ComponentId, data: *const u8) -> &mut Self {
+        let layout = self.world.components[id.index()].type_info.layout();
+        self.discard(id);
+        self.removed.retain(|x| *x != id);
+        let dst = self.world.allocator.allocate(layout).unwrap().as_ptr() as *mut u8;
+        ptr::copy_nonoverlapping(data, dst, layout.size());
+        self.added.push((id, dst));
+        return self;
+    }
+
+    pub fn remove<'b, T>(&mut self) -> &mut Self
+    where
+        T: Bundle<'b>,
+    {
+        // components the world never saw can't be on the entity
+        for type_id in T::type_ids().iter() {
+            if let Some(id) = self.world.component_ids.get(type_id).copied() {
+                self.remove_by_id(id);
+            }
+        }
+        return self;
+    }
+
+    pub fn remove_by_id(&mut self, id: ComponentId) -> &mut Self {
+        self.discard(id);
+        if !self.removed.contains(&id) {
+            self.removed.push(id);
+        }
+        return self;
+    }
+
+    //drops a buffered component that hasn't been applied yet
+    fn discard(&mut self, id: ComponentId) -> () {
+        let Some(index) = self.added.iter().position(|(x, _)| *x == id) else {
+            return;
+        };
+        let (_, data) = self.added.swap_remove(index);
+        let ty = self.world.components[id.index()].type_info;
+        unsafe {
+            ty.drop(data);
+            self.world
+                .allocator
+                .deallocate(NonNull::new(data).unwrap(), ty.layout());
+        }
+    }
+}
+
+impl<'w, A> Drop for EntityMut<'w, A>
+where
+    A: WorldAllocator,
+{
+    fn drop(&mut self) {
+        if self.added.is_empty() && self.removed.is_empty() {
+            return;
+        }
+        unsafe {
+            self.world.relocate(self.entity, &self.added, &self.removed);
+            for (id, data) in self.added.drain(..) {
+                let layout = self.world.components[id.index()].type_info.layout();
+                self.world
+                    .allocator
+                    .deallocate(NonNull::new(data).unwrap(), layout);
+            }
+        }
+        self.world.flush();
     }
 }
 
 #[cfg(test)]
 mod test {
+    use crate::component::{Component, StorageType};
     use std::alloc::Global;
     use std::assert_eq;
     use std::collections::HashSet;
+    use std::rc::Rc;
     use std::vec::Vec;
 
     type World = super::World<Global>;
@@ -215,6 +1110,8 @@ mod test {
         b: u32,
     }
 
+    impl Component for TestComponent {}
+
     #[test]
     fn world_add() {
         let mut w = World::new();
@@ -374,4 +1271,587 @@ mod test {
 
         assert_eq!(count, 20);
     }
+
+    #[test]
+    fn world_query_order() {
+        let build = || {
+            let mut w = World::new();
+            for i in 0..40u32 {
+                let e = w.spawn_with((i,));
+                match i % 4 {
+                    0 => w.add(e, (i as u8,)),
+                    1 => w.add(e, (i as u64, i as u16)),
+                    2 => w.add(e, (i as u16,)),
+                    _ => {}
+                }
+                if i % 7 == 0 {
+                    w.destroy(e);
+                }
+            }
+            return w;
+        };
+        let order = |w: &World| w.query::<(u32,)>().copied().collect::<Vec<_>>();
+        let (a, b) = (build(), build());
+        assert_eq!(order(&a), order(&b));
+        // the archetype of `u32` alone came first, then the ones in spawn order
+        assert_eq!(order(&a)[..3], [3, 11, 15]);
+    }
+
+    #[test]
+    fn world_spawn_with() {
+        let mut w = World::new();
+        let test_data = (TestComponent { a: 4, b: 3 }, 3u32);
+        let e = w.spawn_with(test_data);
+        let tu = w.get::<(TestComponent, u32)>(e).unwrap();
+        assert_eq!(*tu.0, test_data.0);
+        assert_eq!(*tu.1, test_data.1);
+    }
+
+    #[test]
+    fn world_entity_mut() {
+        let mut w = World::new();
+        let e = w.spawn_with((3u32, 4u64));
+        w.entity_mut(e)
+            .insert((TestComponent { a: 1, b: 2 },))
+            .insert((5u8,))
+            .remove::<(u64,)>();
+
+        assert!(w.has::<(u32, TestComponent, u8)>(e));
+        assert!(!w.has::<(u64,)>(e));
+        assert_eq!(
+            *w.get::<(TestComponent,)>(e).unwrap(),
+            TestComponent { a: 1, b: 2 }
+        );
+        assert_eq!(*w.get::<(u8,)>(e).unwrap(), 5);
+        // the entity moved straight into its final archetype
+        let ids = [
+            w.component_id::<u32>().unwrap(),
+            w.component_id::<TestComponent>().unwrap(),
+        ];
+        assert!(!w.archetypes.has(&w.sorted_components(&ids).0));
+    }
+
+    #[test]
+    fn world_entity_mut_replace() {
+        let mut w = World::new();
+        let e = w.spawn_with((3u32,));
+        w.entity_mut(e).remove::<(u32,)>().insert((7u32,));
+        assert_eq!(*w.get::<(u32,)>(e).unwrap(), 7);
+        w.entity_mut(e).insert((8u32,)).remove::<(u32,)>();
+        assert!(!w.has::<(u32,)>(e));
+    }
+
+    #[test]
+    fn world_entity_mut_drops() {
+        let counter = Rc::new(());
+        let mut w = World::new();
+        let e = w.spawn_with((counter.clone(), 1u32));
+        let e2 = w.spawn_with((counter.clone(), 2u32));
+        w.entity_mut(e).insert((counter.clone(),)).insert((4u64,));
+        assert_eq!(Rc::strong_count(&counter), 3);
+        w.remove::<(Rc<()>,)>(e);
+        assert_eq!(Rc::strong_count(&counter), 2);
+        assert_eq!(*w.get::<(u32,)>(e2).unwrap(), 2);
+        drop(w);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn world_spawn_batch() {
+        let mut w = World::new();
+        let entities = w
+            .spawn_batch((0..1000).map(|i| (i, TestComponent { a: 1, b: i })))
+            .collect::<Vec<_>>();
+        assert_eq!(entities.len(), 1000);
+
+        let archetype = w.archetypes.get_by_entity(entities[0]).unwrap();
+        assert_eq!(archetype.len(), 1000);
+        assert_eq!(archetype.capacity(), 1000);
+
+        for (i, e) in entities.into_iter().enumerate() {
+            let (u, t) = w.get::<(u32, TestComponent)>(e).unwrap();
+            assert_eq!(*u, i as u32);
+            assert_eq!(*t, TestComponent { a: 1, b: i as u32 });
+        }
+    }
+
+    #[test]
+    fn world_spawn_batch_reserves_once() {
+        use core::{
+            alloc::{AllocError, Allocator, Layout},
+            cell::Cell,
+            ptr::NonNull,
+        };
+
+        // counts the allocations it hands out, growing included
+        #[derive(Clone, Default)]
+        struct Counting(Rc<Cell<usize>>);
+
+        unsafe impl Allocator for Counting {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+                self.0.set(self.0.get() + 1);
+                return Global.allocate(layout);
+            }
+
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                Global.deallocate(ptr, layout);
+            }
+        }
+
+        let counting = Counting::default();
+        let mut w = super::World::new_in(counting.clone());
+        w.spawn_with((0u32, 0u64));
+        let before = counting.0.get();
+        let entities = w
+            .spawn_batch((0..1000).map(|i| (i as u32, i as u64)))
+            .collect::<Vec<_>>();
+        // a single grow per column
+        assert_eq!(counting.0.get() - before, 2);
+        let archetype = w.archetypes.get_by_entity(entities[0]).unwrap();
+        assert_eq!(archetype.capacity(), 1001);
+        assert_eq!(*w.get::<u64>(entities[999]).unwrap(), 999);
+    }
+
+    #[test]
+    fn world_spawn_batch_sparse() {
+        struct Selected(u32);
+        impl Component for Selected {
+            const STORAGE: StorageType = StorageType::Sparse;
+        }
+
+        let mut w = World::new();
+        let entities = w
+            .spawn_batch((0..10).map(|i| (i, Selected(i))))
+            .collect::<Vec<_>>();
+        let only_sparse = w.spawn_batch([(Selected(10),)]).collect::<Vec<_>>();
+        let archetype = w.archetypes.get_by_entity(only_sparse[0]).unwrap();
+        assert!(archetype.component_ids.is_empty());
+        assert_eq!(w.get::<Selected>(only_sparse[0]).unwrap().0, 10);
+        for (i, e) in entities.into_iter().enumerate() {
+            let (u, selected) = w.get::<(u32, Selected)>(e).unwrap();
+            assert_eq!((*u, selected.0), (i as u32, i as u32));
+        }
+        assert_eq!(w.query::<Selected>().count(), 11);
+    }
+
+    #[test]
+    fn world_spawn_batch_existing() {
+        let mut w = World::new();
+        let e = w.spawn_with((TestComponent { a: 1, b: 2 }, 2u32));
+        let entities = w
+            .spawn_batch((0..20).map(|i| (i, TestComponent { a: 3, b: i })))
+            .collect::<Vec<_>>();
+        assert!(!entities.contains(&e));
+        assert_eq!(w.query::<(u32, TestComponent)>().count(), 21);
+        assert_eq!(*w.get::<(u32,)>(e).unwrap(), 2);
+    }
+
+    #[test]
+    fn world_add_batch() {
+        let counter = Rc::new(());
+        let mut w = World::new();
+        let a = w
+            .spawn_batch((0..10).map(|i| (i as u32,)))
+            .collect::<Vec<_>>();
+        let b = w
+            .spawn_batch((0..10).map(|i| (i as u32, i as u64)))
+            .collect::<Vec<_>>();
+        w.add_batch(a.iter().chain(b.iter()).map(|x| (*x, (counter.clone(),))));
+        assert_eq!(Rc::strong_count(&counter), 21);
+        assert_eq!(w.archetypes.get_by_entity(a[0]).unwrap().len(), 10);
+        assert_eq!(w.archetypes.get_by_entity(b[0]).unwrap().len(), 10);
+
+        let mut found = w
+            .query::<(u32, Rc<()>)>()
+            .with_entities()
+            .map(|(e, (u, _))| (e, *u))
+            .collect::<Vec<_>>();
+        found.sort();
+        let expected = a.iter().chain(b.iter()).zip((0..10).chain(0..10));
+        assert_eq!(found, expected.map(|(e, i)| (*e, i)).collect::<Vec<_>>());
+
+        // adding again replaces the values in place
+        w.add_batch(a.iter().map(|x| (*x, (counter.clone(),))));
+        assert_eq!(Rc::strong_count(&counter), 21);
+    }
+
+    #[test]
+    fn world_despawn_batch() {
+        let counter = Rc::new(());
+        let mut w = World::new();
+        let bullets = w
+            .spawn_batch((0..10).map(|i| (i as u32, counter.clone())))
+            .collect::<Vec<_>>();
+        let others = w
+            .spawn_batch((0..10).map(|i| (i as u64, counter.clone())))
+            .collect::<Vec<_>>();
+        assert_eq!(Rc::strong_count(&counter), 21);
+
+        w.despawn_batch(&bullets);
+        assert_eq!(Rc::strong_count(&counter), 11);
+        assert!(w.archetypes.get_by_entity(bullets[0]).is_none());
+
+        w.despawn_batch(&[others[0], others[5], others[9]]);
+        assert_eq!(Rc::strong_count(&counter), 8);
+        for (i, e) in others.into_iter().enumerate() {
+            match i {
+                0 | 5 | 9 => assert!(!w.has::<(u64,)>(e)),
+                _ => assert_eq!(*w.get::<(u64,)>(e).unwrap(), i as u64),
+            }
+        }
+    }
+
+    #[test]
+    fn world_despawn_matching() {
+        struct Bullet;
+        impl Component for Bullet {}
+
+        let mut w = World::new();
+        let bullets = w
+            .spawn_batch((0..10).map(|i| (i as u32, Bullet)))
+            .collect::<Vec<_>>();
+        let tagged = w.spawn_with((Bullet, 3u64));
+        let other = w.spawn_with((4u32,));
+
+        w.despawn_matching::<(Bullet,)>();
+        assert!(!w.has::<(Bullet,)>(bullets[0]));
+        assert!(!w.has::<(Bullet,)>(tagged));
+        assert!(!w.entities.contains(&tagged));
+        assert_eq!(*w.get::<(u32,)>(other).unwrap(), 4);
+        assert_eq!(w.query::<(u32,)>().count(), 1);
+    }
+
+    #[test]
+    fn world_despawn_matching_empty() {
+        let mut w = World::new();
+        let a = w.spawn_with((1u32,));
+        let b = w.spawn_with((2u64,));
+        assert_eq!(w.query::<()>().count(), 2);
+        w.despawn_matching::<()>();
+        assert_eq!(w.query::<()>().count(), 2);
+        assert_eq!(*w.get::<u32>(a).unwrap(), 1);
+        assert_eq!(*w.get::<u64>(b).unwrap(), 2);
+    }
+
+    #[test]
+    fn world_clear() {
+        let counter = Rc::new(());
+        let mut w = World::new();
+        let entities = w
+            .spawn_batch((0..100).map(|i| (i as u32, counter.clone())))
+            .collect::<Vec<_>>();
+        w.clear();
+        assert_eq!(Rc::strong_count(&counter), 1);
+        assert!(!w.has::<(u32,)>(entities[0]));
+        assert_eq!(w.query::<(u32, Rc<()>)>().count(), 0);
+
+        let e = w.spawn_with((5u32, counter.clone()));
+        assert_ne!(e, entities[0]);
+        assert_eq!(*w.get::<(u32,)>(e).unwrap(), 5);
+    }
+
+    #[test]
+    fn world_compact() {
+        let mut w = World::new();
+        let entities = w
+            .spawn_batch((0..100).map(|i| (i as u32,)))
+            .collect::<Vec<_>>();
+        let e = w.spawn_with((3u64,));
+        w.despawn_batch(&entities[..98]);
+        w.destroy(e);
+        w.compact();
+
+        assert!(!w.archetypes.has(&[w.component_id::<u64>().unwrap()]));
+        let archetype = w
+            .archetypes
+            .get(&[w.component_id::<u32>().unwrap()])
+            .unwrap();
+        assert_eq!(archetype.capacity(), 2);
+        assert_eq!(*w.get::<(u32,)>(entities[98]).unwrap(), 98);
+        assert_eq!(*w.get::<(u32,)>(entities[99]).unwrap(), 99);
+    }
+
+    #[test]
+    fn world_reserve() {
+        let mut w = World::new();
+        w.reserve::<(TestComponent, u32)>(500);
+        let ids = [
+            w.component_id::<u32>().unwrap(),
+            w.component_id::<TestComponent>().unwrap(),
+        ];
+        let archetype = w.archetypes.get(&w.sorted_components(&ids).0).unwrap();
+        assert_eq!(archetype.capacity(), 500);
+
+        let entities = w
+            .spawn_batch((0..500).map(|i| (i, TestComponent { a: 0, b: i })))
+            .collect::<Vec<_>>();
+        let archetype = w.archetypes.get_by_entity(entities[0]).unwrap();
+        assert_eq!(archetype.capacity(), 500);
+
+        w.reserve::<(u32, TestComponent)>(1000);
+        let archetype = w.archetypes.get_by_entity(entities[0]).unwrap();
+        assert_eq!(archetype.capacity(), 1500);
+        assert_eq!(*w.get::<(u32,)>(entities[499]).unwrap(), 499);
+    }
+
+    #[test]
+    fn world_query_subset() {
+        let mut w = World::new();
+        assert_eq!(w.query::<(u32,)>().count(), 0);
+
+        w.spawn_with((TestComponent { a: 1, b: 1 }, 3u32));
+        w.spawn_with((TestComponent { a: 2, b: 2 },));
+        w.spawn_with((4u32, 5u64));
+        w.spawn_with((TestComponent { a: 3, b: 3 }, 6u32, 7u64));
+
+        assert_eq!(w.query::<(TestComponent, u32)>().count(), 2);
+        assert_eq!(w.query::<(u32,)>().count(), 3);
+        assert_eq!(w.query_mut::<(u64, TestComponent)>().count(), 1);
+        assert_eq!(w.query::<(u8,)>().count(), 0);
+    }
+
+    #[test]
+    fn world_register_component() {
+        struct Named;
+        impl Component for Named {
+            const STORAGE: StorageType = StorageType::Sparse;
+            fn name() -> &'static str {
+                return "Named";
+            }
+        }
+
+        let mut w = World::new();
+        let e = w.spawn_with((Named, 3u32));
+        w.register_component::<Named>();
+        let e2 = w.spawn_with((Named, 3u64));
+
+        let id = w.component_id::<Named>().unwrap();
+        let info = w.sparse.get(id).unwrap().type_info();
+        assert_eq!(info.name(), "Named");
+        assert_eq!(info.storage(), StorageType::Sparse);
+        assert!(w.has::<Named>(e) && w.has::<Named>(e2));
+    }
+
+    #[test]
+    #[should_panic(expected = "contains the component `u32` more than once")]
+    fn world_add_duplicate() {
+        let mut w = World::new();
+        let e = w.spawn();
+        w.add(e, (3u32, 4u32));
+    }
+
+    #[test]
+    #[should_panic(expected = "contains the component `u64` more than once")]
+    fn world_spawn_batch_duplicate() {
+        let mut w = World::new();
+        let _ = w.spawn_batch((0..10).map(|i| (i as u64, 3u32, i as u64)));
+    }
+
+    #[test]
+    #[should_panic(expected = "contains the component `u32` more than once")]
+    fn world_query_mut_duplicate() {
+        let mut w = World::new();
+        w.spawn_with((3u32,));
+        for _ in w.query_mut::<(u32, u32)>() {}
+    }
+
+    #[test]
+    fn world_plain_components() {
+        use crate::component::StorageType;
+
+        // no `Component` impl, so only usable inside a tuple
+        #[derive(Debug, PartialEq)]
+        struct Plain(u32);
+
+        struct Marked;
+        impl Component for Marked {
+            const STORAGE: StorageType = StorageType::Sparse;
+        }
+
+        let mut w = World::new();
+        let e = w.spawn_with((Plain(1), Marked, 2u32));
+        assert_eq!(*w.get::<(Plain,)>(e).unwrap(), Plain(1));
+        assert!(w.has::<(Plain, Marked, u32)>(e));
+
+        // the metadata of a component still applies when it is part of a tuple
+        let id = w.component_id::<Marked>().unwrap();
+        assert!(w.is_sparse(id));
+        assert_eq!(
+            w.archetypes.get_by_entity(e).unwrap().component_ids.len(),
+            2
+        );
+        for (plain, x) in w.query_mut::<(Plain, u32)>() {
+            plain.0 += *x;
+        }
+        assert_eq!(*w.get::<(Plain,)>(e).unwrap(), Plain(3));
+    }
+
+    #[test]
+    fn world_dynamic_components() {
+        use core::{
+            alloc::Layout,
+            sync::atomic::{AtomicUsize, Ordering},
+        };
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+        unsafe fn count_drop(_: *mut u8) -> () {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let mut w = World::new();
+        let health = w.register_dynamic_component("Health", Layout::new::<u32>(), Some(count_drop));
+        let tag = w.register_dynamic_component("Tag", Layout::new::<()>(), None);
+        assert_eq!(
+            w.component_info_by_id(health).unwrap().type_info.name(),
+            "Health"
+        );
+
+        let a = w.spawn_with((1u64,));
+        let b = w.spawn();
+        unsafe {
+            w.insert_by_id(a, health, &10u32 as *const u32 as *const u8);
+            w.insert_by_id(b, health, &20u32 as *const u32 as *const u8);
+            w.insert_by_id(b, tag, &() as *const () as *const u8);
+        }
+        assert_eq!(*w.get::<u64>(a).unwrap(), 1);
+        assert_eq!(
+            unsafe { *(w.get_by_id(a, health).unwrap() as *const u32) },
+            10
+        );
+        assert!(w.get_by_id(a, tag).is_none());
+
+        let u64_id = w.component_id::<u64>().unwrap();
+        let mut found = Vec::new();
+        for (e, data) in w.query_by_ids(&[health, u64_id]) {
+            found.push((e, unsafe { *(data[0] as *const u32) }));
+        }
+        assert_eq!(found, [(a, 10)]);
+
+        for (_, data) in w.query_by_ids(&[health]) {
+            unsafe { *(data[0] as *mut u32) += 1 };
+        }
+        assert_eq!(
+            unsafe { *(w.get_by_id(b, health).unwrap() as *const u32) },
+            21
+        );
+
+        w.remove_by_id(b, health).unwrap();
+        assert_eq!(DROPPED.load(Ordering::Relaxed), 1);
+        assert!(w.get_by_id(b, health).is_none());
+        assert!(w.get_by_id(b, tag).is_some());
+        assert!(w.remove_by_id(b, health).is_none());
+    }
+
+    #[test]
+    fn world_sparse_components() {
+        #[derive(Debug, PartialEq)]
+        struct Selected(u32);
+        impl Component for Selected {
+            const STORAGE: StorageType = StorageType::Sparse;
+        }
+
+        let mut w = World::new();
+        let a = w.spawn_with((1u32, 2u64));
+        let b = w.spawn_with((3u32, Selected(1)));
+        let c = w.spawn_with((Selected(2),));
+        let archetype = w.archetypes.get_by_entity(a).unwrap() as *const _;
+
+        // toggling a sparse component keeps the entity in its archetype
+        w.add(a, (Selected(3),));
+        assert!(core::ptr::eq(
+            w.archetypes.get_by_entity(a).unwrap(),
+            archetype
+        ));
+        assert_eq!(*w.get::<Selected>(a).unwrap(), Selected(3));
+        w.remove::<Selected>(a).unwrap();
+        assert!(core::ptr::eq(
+            w.archetypes.get_by_entity(a).unwrap(),
+            archetype
+        ));
+        assert!(!w.has::<Selected>(a));
+
+        // queries join table and sparse storage
+        let mut found = w
+            .query::<(u32, Selected)>()
+            .map(|(u, s)| (*u, s.0))
+            .collect::<Vec<_>>();
+        found.sort();
+        assert_eq!(found, [(3, 1)]);
+        for s in w.query_mut::<Selected>() {
+            s.0 += 10;
+        }
+        assert_eq!(*w.get::<Selected>(c).unwrap(), Selected(12));
+        assert_eq!(*w.get::<(u32, Selected)>(b).unwrap().1, Selected(11));
+
+        w.destroy(b);
+        assert_eq!(w.query::<Selected>().count(), 1);
+        w.despawn_matching::<Selected>();
+        assert!(!w.has::<Selected>(c));
+        assert_eq!(w.query::<u32>().count(), 1);
+    }
+
+    #[test]
+    fn world_hooks() {
+        use super::DeferredWorld;
+        use crate::{component::ComponentHooks, entity::Entity, world::WorldAllocator};
+        use std::{cell::RefCell, thread_local};
+
+        thread_local! {
+            static LOG: RefCell<Vec<(&'static str, Entity, u32)>> = const { RefCell::new(Vec::new()) };
+        }
+        fn log<A: WorldAllocator>(event: &'static str, world: &DeferredWorld<A>, entity: Entity) {
+            let value = world.get::<Indexed>(entity).unwrap().0;
+            LOG.with(|x| x.borrow_mut().push((event, entity, value)));
+        }
+        fn take() -> Vec<(&'static str, Entity, u32)> {
+            return LOG.with(|x| x.take());
+        }
+
+        struct Indexed(u32);
+        impl Component for Indexed {
+            fn register_hooks<A: WorldAllocator>(hooks: &mut ComponentHooks<A>) -> () {
+                hooks
+                    .on_add(|w, e| log("add", w, e))
+                    .on_insert(|w, e| log("insert", w, e))
+                    .on_remove(|w, e| {
+                        log("remove", w, e);
+                        // structural changes wait until the removal is done
+                        w.commands().spawn((e.0 as u32,));
+                    });
+            }
+        }
+
+        // the hooks are picked up without registering `Indexed` first
+        let mut w = World::new();
+        let a = w.spawn_with((Indexed(1), 0u8));
+        assert_eq!(take(), [("add", a, 1), ("insert", a, 1)]);
+        w.add(a, (Indexed(2),));
+        assert_eq!(take(), [("insert", a, 2)]);
+        w.remove::<Indexed>(a);
+        assert_eq!(take(), [("remove", a, 2)]);
+        assert_eq!(w.query::<u32>().count(), 1);
+
+        let b = w.spawn_with((Indexed(3),));
+        take();
+        w.destroy(b);
+        assert_eq!(take(), [("remove", b, 3)]);
+        let c = w.spawn_with((Indexed(4),));
+        take();
+        let batch = w
+            .spawn_batch([(Indexed(5), 0u8), (Indexed(6), 0u8)])
+            .collect::<Vec<_>>();
+        let (d, e) = (batch[0], batch[1]);
+        assert_eq!(
+            take(),
+            [
+                ("add", d, 5),
+                ("insert", d, 5),
+                ("add", e, 6),
+                ("insert", e, 6)
+            ]
+        );
+        w.despawn_batch(&batch);
+        take();
+        drop(w);
+        assert_eq!(take(), [("remove", c, 4)]);
+    }
 }
@@ -11,4 +11,7 @@ fn main() {
     let e = w.spawn();
     let b = B { a: 1 };
     w.add(e, b);
+    for b in w.query::<B>() {
+        println!("{}", b.a);
+    }
 }
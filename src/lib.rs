@@ -5,13 +5,18 @@ pub use perplecs_macros::*;
 
 pub mod prelude {
 
+    #[cfg(feature = "transform")]
+    pub use perplecs_ecs::transform::{GlobalTransform, LocalTransform, Transform};
     pub use perplecs_ecs::{
         bundle::Bundle,
+        component::{Component, ComponentId, StorageType},
         entity::Entity,
+        hierarchy::{Children, Parent},
+        observer::{Event, Observable, OnAdd, OnInsert, OnRemove, Trigger},
+        prefab::Prefab,
         query::{Query, QueryMut},
-        world::World,
     };
-    pub use perplecs_macros::Bundle;
+    pub use perplecs_macros::{Bundle, Component};
     #[cfg(feature = "std")]
     pub type World = perplecs_ecs::world::World<std::alloc::Global>;
 }
@@ -0,0 +1,168 @@
+#![cfg(feature = "std")]
+
+use perplecs::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Position(f32, f32);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Velocity(f32, f32);
+
+#[derive(Bundle)]
+struct Body {
+    position: Position,
+    velocity: Velocity,
+}
+
+#[test]
+fn derive_bundle_get() -> () {
+    let mut world = World::new();
+    let e = world.spawn_with(Body {
+        position: Position(1.0, 2.0),
+        velocity: Velocity(3.0, 4.0),
+    });
+
+    let body = world.get::<Body>(e).unwrap();
+    assert_eq!(*body.position, Position(1.0, 2.0));
+    assert_eq!(*body.velocity, Velocity(3.0, 4.0));
+
+    let body = world.get_mut::<Body>(e).unwrap();
+    body.position.0 += 1.0;
+    assert_eq!(*world.get::<(Position,)>(e).unwrap(), Position(2.0, 2.0));
+}
+
+#[test]
+fn derive_bundle_query() -> () {
+    let mut world = World::new();
+    for i in 0..10 {
+        world.spawn_with(Body {
+            position: Position(i as f32, 0.0),
+            velocity: Velocity(1.0, 1.0),
+        });
+    }
+    world.spawn_with((Position(0.0, 0.0),));
+
+    for body in world.query_mut::<Body>() {
+        body.position.0 += body.velocity.0;
+        body.position.1 += body.velocity.1;
+    }
+
+    let mut count = 0;
+    for body in world.query::<Body>() {
+        assert_eq!(body.position.1, 1.0);
+        count += 1;
+    }
+    assert_eq!(count, 10);
+    assert_eq!(world.query::<(Position,)>().count(), 11);
+}
+
+#[derive(Bundle)]
+struct Pair(Position, Velocity);
+
+#[derive(Bundle)]
+struct Generic<T: Copy, const N: usize>
+where
+    T: PartialEq,
+{
+    value: T,
+    values: [u8; N],
+}
+
+#[test]
+fn derive_bundle_tuple_struct() -> () {
+    let mut world = World::new();
+    let e = world.spawn_with(Pair(Position(1.0, 1.0), Velocity(2.0, 2.0)));
+    let pair = world.get::<Pair>(e).unwrap();
+    assert_eq!(*pair.0, Position(1.0, 1.0));
+    assert_eq!(*pair.1, Velocity(2.0, 2.0));
+    assert_eq!(world.query::<Pair>().count(), 1);
+}
+
+#[test]
+fn derive_bundle_generic() -> () {
+    let mut world = World::new();
+    let e = world.spawn_with(Generic {
+        value: 3u32,
+        values: [1u8, 2, 3],
+    });
+    let generic = world.get_mut::<Generic<u32, 3>>(e).unwrap();
+    *generic.value += 1;
+    generic.values[0] = 7;
+
+    let generic = world.get::<Generic<u32, 3>>(e).unwrap();
+    assert_eq!(*generic.value, 4);
+    assert_eq!(*generic.values, [7, 2, 3]);
+    assert!(world.get::<Generic<u64, 3>>(e).is_none());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Health(u32);
+
+#[derive(Bundle)]
+struct Player {
+    #[bundle]
+    body: Body,
+    health: Health,
+}
+
+#[derive(Bundle)]
+struct Boss {
+    #[bundle(flatten)]
+    player: Player,
+    name: &'static str,
+}
+
+#[derive(Bundle)]
+struct Duplicated {
+    #[bundle]
+    body: Body,
+    position: Position,
+}
+
+#[test]
+fn derive_bundle_flatten() -> () {
+    let mut world = World::new();
+    let e = world.spawn_with(Boss {
+        player: Player {
+            body: Body {
+                position: Position(1.0, 2.0),
+                velocity: Velocity(3.0, 4.0),
+            },
+            health: Health(100),
+        },
+        name: "boss",
+    });
+
+    assert!(world.has::<(Position, Velocity, Health, &'static str)>(e));
+    let boss = world.get::<Boss>(e).unwrap();
+    assert_eq!(*boss.player.body.position, Position(1.0, 2.0));
+    assert_eq!(*boss.player.body.velocity, Velocity(3.0, 4.0));
+    assert_eq!(*boss.player.health, Health(100));
+    assert_eq!(*boss.name, "boss");
+
+    for player in world.query_mut::<Player>() {
+        player.health.0 -= 10;
+    }
+    assert_eq!(*world.get::<(Health,)>(e).unwrap(), Health(90));
+}
+
+#[test]
+#[should_panic(expected = "bundle `Duplicated` contains the component")]
+fn derive_bundle_flatten_duplicate() -> () {
+    let mut world = World::new();
+    world.spawn_with(Duplicated {
+        body: Body {
+            position: Position(1.0, 2.0),
+            velocity: Velocity(3.0, 4.0),
+        },
+        position: Position(0.0, 0.0),
+    });
+}
+
+#[test]
+fn derive_bundle_ui() -> () {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+    // duplicates spelled differently compile and are caught at runtime
+    t.pass("tests/ui/pass/*.rs");
+}
@@ -0,0 +1,71 @@
+#![cfg(feature = "std")]
+
+use std::any::TypeId;
+
+use perplecs::{
+    prelude::*,
+    world::{DeferredWorld, WorldAllocator},
+};
+
+#[derive(Component, Debug, PartialEq)]
+#[component(name = "Health", on_add = health_added, on_remove = health_removed)]
+struct Health(u32);
+
+#[derive(Component)]
+#[component(storage = "sparse")]
+struct Stunned;
+
+#[derive(Component)]
+struct Position;
+
+#[derive(Component, Debug, PartialEq)]
+#[component(on_add = armor_added)]
+struct Armor(u32);
+
+fn armor_added<A: WorldAllocator>(world: &mut DeferredWorld<A>, entity: Entity) {
+    world.get_mut::<Armor>(entity).unwrap().0 *= 2;
+}
+
+fn health_added<A: WorldAllocator>(_world: &mut DeferredWorld<A>, _entity: Entity) {}
+
+fn health_removed<A: WorldAllocator>(_world: &mut DeferredWorld<A>, _entity: Entity) {}
+
+#[test]
+fn derive_component_metadata() -> () {
+    assert_eq!(Health::name(), "Health");
+    assert_eq!(Health::STORAGE, StorageType::Table);
+    assert_eq!(Stunned::STORAGE, StorageType::Sparse);
+    assert!(Position::name().ends_with("Position"));
+}
+
+#[test]
+fn derive_component_register() -> () {
+    let mut world = World::new();
+    let e = world.spawn_with((Health(3), Stunned));
+    world.register_component::<Health>();
+    world.register_component::<Stunned>();
+
+    let health = world.component_info(TypeId::of::<Health>()).unwrap();
+    assert_eq!(health.type_info.name(), "Health");
+    assert!(health.hooks.on_add.is_some());
+    assert!(health.hooks.on_insert.is_none());
+    assert!(health.hooks.on_remove.is_some());
+
+    let stunned = world.component_info(TypeId::of::<Stunned>()).unwrap();
+    assert_eq!(stunned.type_info.storage(), StorageType::Sparse);
+    assert!(world.component_info(TypeId::of::<Position>()).is_none());
+    assert_eq!(*world.get::<(Health,)>(e).unwrap(), Health(3));
+}
+
+#[test]
+fn derive_component_hooks_unregistered() -> () {
+    // the hooks come along the first time the component shows up
+    let mut world = World::new();
+    let e = world.spawn_with((Armor(3),));
+    assert_eq!(*world.get::<Armor>(e).unwrap(), Armor(6));
+    world.add(e, Armor(4));
+    assert_eq!(*world.get::<Armor>(e).unwrap(), Armor(4));
+
+    let e = world.spawn_with((Health(1), Armor(5)));
+    assert_eq!(*world.get::<Armor>(e).unwrap(), Armor(10));
+}
@@ -0,0 +1,10 @@
+use perplecs::prelude::*;
+
+#[derive(Bundle)]
+struct Duplicated {
+    a: u32,
+    b: u64,
+    c: u32,
+}
+
+fn main() {}
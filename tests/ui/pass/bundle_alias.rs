@@ -0,0 +1,18 @@
+use perplecs::prelude::*;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+type Meters = u32;
+
+#[derive(Bundle)]
+struct Aliased {
+    a: u32,
+    b: Meters,
+}
+
+fn main() {
+    // the panic is expected, keep it out of the test output
+    std::panic::set_hook(Box::new(|_| {}));
+    let mut world = World::new();
+    let spawn = || world.spawn_with(Aliased { a: 1, b: 2 });
+    assert!(catch_unwind(AssertUnwindSafe(spawn)).is_err());
+}